@@ -0,0 +1,123 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ruint::aliases::U256;
+use solana_sdk::pubkey::Pubkey;
+
+use noierrdev_tokenmill_swap_sample::market::{Market, MarketSettings};
+use noierrdev_tokenmill_swap_sample::math::{mul_div, mul_div_round_up};
+use noierrdev_tokenmill_swap_sample::quote::{quote, PreparedMarket};
+use noierrdev_tokenmill_swap_sample::swap_math::{
+    get_amount_0, get_amount_1, get_delta_amounts, get_next_sqrt_ratio_from_amount_0,
+    get_next_sqrt_ratio_from_amount_1,
+};
+
+// Price going from 1e-6 to 9e-6 token 1 per token 0 over phase A, with about 800M tokens of 6 decimals in it
+const SQRT_PRICE_A_X96: u128 = 79_228_162_514_264_337_593_543_950;
+const SQRT_PRICE_B_X96: u128 = 3 * SQRT_PRICE_A_X96;
+const LIQUIDITY_A: u128 = 1_200_000_000_000;
+const LIQUIDITY_B: u128 = 4_000_000_000_000;
+
+fn market() -> Market {
+    Market {
+        discriminator: [219, 190, 213, 55, 0, 227, 198, 154],
+        config: Pubkey::default(),
+        creator: Pubkey::default(),
+        swap_authority: None,
+        token_mint0: Pubkey::default(),
+        token_mint1: Pubkey::default(),
+        reserve0: Pubkey::default(),
+        reserve1: Pubkey::default(),
+        fee_reserve: None,
+        fee_reserve_last_update: 0,
+        settings: MarketSettings {
+            max_supply: 1_000_000_000_000_000,
+            sqrt_price_a_x96: SQRT_PRICE_A_X96,
+            sqrt_price_b_x96: SQRT_PRICE_B_X96,
+            liquidity_a: LIQUIDITY_A,
+            liquidity_b: LIQUIDITY_B,
+            fee: 10_000,
+        },
+        sqrt_price_x96: 2 * SQRT_PRICE_A_X96,
+        bump: [255],
+    }
+}
+
+fn math(c: &mut Criterion) {
+    let x = U256::from(LIQUIDITY_A) << 96;
+    let y = U256::from(SQRT_PRICE_A_X96);
+    let denominator = U256::from(SQRT_PRICE_A_X96) * U256::from(SQRT_PRICE_B_X96);
+    let large = U256::MAX >> 8;
+
+    c.bench_function("mul_div", |b| b.iter(|| mul_div(black_box(x), black_box(y), black_box(denominator))));
+    c.bench_function("mul_div_round_up", |b| {
+        b.iter(|| mul_div_round_up(black_box(x), black_box(y), black_box(denominator)))
+    });
+    // Product past 2^256, going through the 512 bits division
+    c.bench_function("mul_div_wide", |b| {
+        b.iter(|| mul_div(black_box(large), black_box(large), black_box(large)))
+    });
+}
+
+fn swap_math(c: &mut Criterion) {
+    let sqrt_price = 2 * SQRT_PRICE_A_X96;
+
+    c.bench_function("get_amount_0", |b| {
+        b.iter(|| get_amount_0(black_box(sqrt_price), black_box(SQRT_PRICE_B_X96), black_box(LIQUIDITY_A), true))
+    });
+    c.bench_function("get_amount_1", |b| {
+        b.iter(|| get_amount_1(black_box(sqrt_price), black_box(SQRT_PRICE_B_X96), black_box(LIQUIDITY_A), true))
+    });
+    c.bench_function("get_next_sqrt_ratio_from_amount_0", |b| {
+        b.iter(|| get_next_sqrt_ratio_from_amount_0(black_box(sqrt_price), black_box(LIQUIDITY_A), black_box(1_000_000_000)))
+    });
+    c.bench_function("get_next_sqrt_ratio_from_amount_1", |b| {
+        b.iter(|| get_next_sqrt_ratio_from_amount_1(black_box(sqrt_price), black_box(LIQUIDITY_A), black_box(1_000_000_000)))
+    });
+    c.bench_function("get_delta_amounts_buy_exact_in", |b| {
+        b.iter(|| {
+            get_delta_amounts(
+                black_box(sqrt_price),
+                black_box(SQRT_PRICE_B_X96),
+                black_box(LIQUIDITY_A),
+                black_box(1_000_000_000),
+                black_box(10_000),
+            )
+        })
+    });
+    c.bench_function("get_delta_amounts_sell_exact_out", |b| {
+        b.iter(|| {
+            get_delta_amounts(
+                black_box(sqrt_price),
+                black_box(SQRT_PRICE_A_X96),
+                black_box(LIQUIDITY_A),
+                black_box(-1_000_000),
+                black_box(10_000),
+            )
+        })
+    });
+}
+
+fn quotes(c: &mut Criterion) {
+    let market = market();
+    let prepared = PreparedMarket::new(&market.settings);
+
+    c.bench_function("quote_buy_exact_in", |b| {
+        b.iter(|| quote(black_box(&market), false, black_box(1_000_000_000), u128::MAX))
+    });
+    // Large enough to cross into phase B
+    c.bench_function("quote_buy_exact_in_crossing", |b| {
+        b.iter(|| quote(black_box(&market), false, black_box(10_000_000_000_000), u128::MAX))
+    });
+    // Sells run a second swap to convert the fee to token 1
+    c.bench_function("quote_sell_exact_in", |b| {
+        b.iter(|| quote(black_box(&market), true, black_box(1_000_000_000), SQRT_PRICE_A_X96))
+    });
+    c.bench_function("prepared_quote_buy_exact_in", |b| {
+        b.iter(|| prepared.quote(black_box(market.sqrt_price_x96), false, black_box(1_000_000_000), u128::MAX))
+    });
+    c.bench_function("prepared_quote_sell_exact_in", |b| {
+        b.iter(|| prepared.quote(black_box(market.sqrt_price_x96), true, black_box(1_000_000_000), SQRT_PRICE_A_X96))
+    });
+}
+
+criterion_group!(benches, math, swap_math, quotes);
+criterion_main!(benches);
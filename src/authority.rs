@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::{json, Value};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+use crate::market::{Market, TOKENMILL_PROGRAM};
+use crate::swap::SWAP_DISCRIMINATOR;
+
+// Positions among the accounts of `swap::build_swap_instruction`
+const SWAP_MARKET_INDEX: usize = 1;
+const SWAP_AUTHORITY_INDEX: usize = 9;
+const SWAP_USER_INDEX: usize = 10;
+
+// Traders and markets a swap authority approves swaps for, a swap needing both its user and its market listed
+// Empty lists approve nothing
+#[derive(Clone, Debug, Default)]
+pub struct SwapAllowlist {
+    pub users: HashSet<Pubkey>,
+    pub markets: HashSet<Pubkey>,
+}
+
+impl SwapAllowlist {
+    pub fn check(&self, user: &Pubkey, market_address: &Pubkey) -> Result<()> {
+        if !self.users.contains(user) {
+            return Err(anyhow!("SwapNotAllowed: {} isn't allowed to trade", user));
+        }
+        if !self.markets.contains(market_address) {
+            return Err(anyhow!("SwapNotAllowed: market {} isn't open to co-signed swaps", market_address));
+        }
+
+        Ok(())
+    }
+}
+
+// Markets created with a swap authority only accept swaps it co-signs, `signers` are the keys able to sign the swap
+pub fn check_swap_access(market_address: &Pubkey, market: &Market, signers: &[Pubkey]) -> Result<()> {
+    match market.swap_authority {
+        Some(swap_authority) if !signers.contains(&swap_authority) => Err(anyhow!(
+            "SwapAuthorityRequired: market {} only accepts swaps co-signed by {}",
+            market_address,
+            swap_authority
+        )),
+        _ => Ok(()),
+    }
+}
+
+// The authority signature covers the whole transaction, so it's only given to transactions where the authority
+// does nothing but approve TokenMill swaps: no fees to pay, no account to write, no other instruction to sign
+// Returns the user and the market of each swap to approve
+pub fn validate_cosign_request(transaction: &Transaction, swap_authority: &Pubkey) -> Result<Vec<(Pubkey, Pubkey)>> {
+    let message = &transaction.message;
+    let header = &message.header;
+    let index = message
+        .account_keys
+        .iter()
+        .position(|key| key == swap_authority)
+        .ok_or(anyhow!("InvalidCosignRequest: {} isn't part of the transaction", swap_authority))?;
+
+    if index == 0 {
+        return Err(anyhow!("InvalidCosignRequest: the swap authority can't pay the fees"));
+    }
+    let num_signers = usize::from(header.num_required_signatures);
+    if index >= num_signers {
+        return Err(anyhow!("InvalidCosignRequest: the swap authority isn't a signer"));
+    }
+    if index < num_signers - usize::from(header.num_readonly_signed_accounts) {
+        return Err(anyhow!("InvalidCosignRequest: the swap authority can't be writable"));
+    }
+
+    let program_id = Pubkey::from_str_const(TOKENMILL_PROGRAM);
+    let mut swaps = vec![];
+    for instruction in message.instructions.iter() {
+        let positions = instruction
+            .accounts
+            .iter()
+            .enumerate()
+            .filter(|(_, account)| usize::from(**account) == index)
+            .map(|(position, _)| position)
+            .collect::<Vec<_>>();
+        if positions.is_empty() {
+            continue;
+        }
+
+        let is_swap = message.account_keys.get(usize::from(instruction.program_id_index)) == Some(&program_id)
+            && instruction.data.starts_with(&SWAP_DISCRIMINATOR);
+        if !is_swap || positions != [SWAP_AUTHORITY_INDEX] {
+            return Err(anyhow!("InvalidCosignRequest: the swap authority is used outside of a swap"));
+        }
+        let account_key = |position: usize| {
+            instruction
+                .accounts
+                .get(position)
+                .and_then(|account| message.account_keys.get(usize::from(*account)))
+                .copied()
+                .ok_or(anyhow!("InvalidCosignRequest: swap without its accounts"))
+        };
+        swaps.push((account_key(SWAP_USER_INDEX)?, account_key(SWAP_MARKET_INDEX)?));
+    }
+    if swaps.is_empty() {
+        return Err(anyhow!("InvalidCosignRequest: no swap to approve"));
+    }
+
+    Ok(swaps)
+}
+
+// Adds the authority signature to a transaction the trader may already have signed, keeping the other signatures
+pub fn cosign_swap_transaction(transaction: &mut Transaction, swap_authority: &Keypair) -> Result<()> {
+    validate_cosign_request(transaction, &swap_authority.pubkey())?;
+
+    let recent_blockhash = transaction.message.recent_blockhash;
+    transaction.try_partial_sign(&[swap_authority], recent_blockhash)?;
+
+    Ok(())
+}
+
+// Sends the transaction to the `/swap/cosign` endpoint of a remote service holding the swap authority
+pub async fn request_cosign(client: &reqwest::Client, cosigner_url: &str, transaction: &Transaction) -> Result<Transaction> {
+    let url = format!("{}/swap/cosign", cosigner_url.trim_end_matches('/'));
+    let response = client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .body(json!({ "transaction": general_purpose::STANDARD.encode(bincode::serialize(transaction)?) }).to_string())
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach the co-signer at {}", url))?;
+    let status = response.status();
+    let body = serde_json::from_str::<Value>(&response.text().await?).context("Invalid co-signer response")?;
+
+    if !status.is_success() {
+        let error = body.get("error").and_then(Value::as_str).unwrap_or("unknown error");
+        return Err(anyhow!("CosignFailed: {} {}", status, error));
+    }
+
+    let encoded = body
+        .get("transaction")
+        .and_then(Value::as_str)
+        .context("Co-signer response has no transaction")?;
+    let cosigned: Transaction = bincode::deserialize(&general_purpose::STANDARD.decode(encoded)?)
+        .context("Invalid co-signed transaction")?;
+
+    // The co-signer could return anything, only its signature is taken
+    if cosigned.message != transaction.message {
+        return Err(anyhow!("CosignFailed: the co-signer changed the transaction"));
+    }
+    let mut transaction = transaction.clone();
+    let message_data = transaction.message_data();
+    let signers = transaction.signatures.iter_mut().zip(transaction.message.account_keys.iter());
+    for ((signature, key), cosigned) in signers.zip(cosigned.signatures) {
+        if cosigned.verify(key.as_ref(), &message_data) {
+            *signature = cosigned;
+        }
+    }
+
+    Ok(transaction)
+}
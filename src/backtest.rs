@@ -0,0 +1,415 @@
+use anyhow::{anyhow, Context, Result};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+use crate::fixtures::SwapFixture;
+use crate::market::Market;
+use crate::price::sqrt_price_x96_to_price;
+use crate::state::{Fill, MarketState};
+use crate::swap::{SwapParameters, MAX_BPS};
+use crate::transaction::decode_swaps;
+
+// A swap of a historical stream
+// The first swap of each market carries the market data right before it, which the replay starts from
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapEvent {
+    pub slot: u64,
+    pub signature: String,
+    pub market: Pubkey,
+    pub parameters: SwapParameters,
+    pub market_data: Option<Vec<u8>>,
+}
+
+impl From<&SwapFixture> for SwapEvent {
+    fn from(fixture: &SwapFixture) -> Self {
+        Self {
+            slot: fixture.slot,
+            signature: fixture.signature.clone(),
+            market: fixture.market_address,
+            parameters: fixture.parameters.clone(),
+            market_data: Some(fixture.market_data.clone()),
+        }
+    }
+}
+
+// Fixtures are stored by signature, so they're put back in slot order, keeping the file order within a slot
+pub fn events_from_fixtures(fixtures: &[SwapFixture]) -> Vec<SwapEvent> {
+    let mut events = fixtures.iter().map(SwapEvent::from).collect::<Vec<_>>();
+    events.sort_by_key(|event| event.slot);
+    events
+}
+
+// Swaps of a confirmed transaction, the market data has to come from elsewhere
+pub fn events_from_transaction(transaction: &EncodedConfirmedTransactionWithStatusMeta) -> Result<Vec<SwapEvent>> {
+    let decoded = transaction.transaction.transaction.decode().context("Failed to decode transaction")?;
+    let signature = decoded.signatures.first().context("Transaction has no signature")?.to_string();
+
+    Ok(decode_swaps(transaction)?
+        .into_iter()
+        .map(|swap| SwapEvent {
+            slot: transaction.slot,
+            signature: signature.clone(),
+            market: swap.market,
+            parameters: swap.parameters,
+            market_data: None,
+        })
+        .collect())
+}
+
+pub const EVENTS_CSV_HEADER: &str = "slot,signature,market,side,mode,amount,threshold,market_data";
+
+// `market_data` is base64 and left empty past the first swap of each market
+pub fn parse_events_csv(content: &str) -> Result<Vec<SwapEvent>> {
+    let mut lines = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    match lines.next() {
+        Some((_, header)) if header.trim() == EVENTS_CSV_HEADER => {}
+        _ => return Err(anyhow!("Invalid CSV header, expected {}", EVENTS_CSV_HEADER)),
+    }
+
+    lines
+        .map(|(index, line)| {
+            let fields = line.trim().split(',').collect::<Vec<_>>();
+            let [slot, signature, market, side, mode, amount, threshold, market_data] = fields[..] else {
+                return Err(anyhow!("Line {}: expected 8 fields, got {}", index + 1, fields.len()));
+            };
+            let u64_field = |value: &str, key: &str| {
+                value.parse::<u64>().map_err(|_| anyhow!("Line {}: invalid {}: {}", index + 1, key, value))
+            };
+
+            Ok(SwapEvent {
+                slot: u64_field(slot, "slot")?,
+                signature: signature.to_string(),
+                market: Pubkey::from_str(market).map_err(|_| anyhow!("Line {}: invalid address: {}", index + 1, market))?,
+                parameters: SwapParameters::parse(side, mode, u64_field(amount, "amount")?, u64_field(threshold, "threshold")?)?,
+                market_data: decode_market_data(market_data).with_context(|| format!("Line {}", index + 1))?,
+            })
+        })
+        .collect()
+}
+
+// An array of objects with the fields of the CSV format
+pub fn parse_events_json(value: &Value) -> Result<Vec<SwapEvent>> {
+    let events = value.as_array().ok_or(anyhow!("Expected an array of swaps"))?;
+
+    events
+        .iter()
+        .enumerate()
+        .map(|(index, event)| {
+            let str_field = |key: &str| -> Result<&str> {
+                event.get(key).and_then(Value::as_str).ok_or(anyhow!("Swap {}: missing {}", index, key))
+            };
+            let u64_field = |key: &str| -> Result<u64> {
+                event.get(key).and_then(Value::as_u64).ok_or(anyhow!("Swap {}: missing {}", index, key))
+            };
+            let market = str_field("market")?;
+
+            Ok(SwapEvent {
+                slot: u64_field("slot")?,
+                signature: str_field("signature")?.to_string(),
+                market: Pubkey::from_str(market).map_err(|_| anyhow!("Swap {}: invalid address: {}", index, market))?,
+                parameters: SwapParameters::parse(
+                    str_field("side")?,
+                    event.get("mode").and_then(Value::as_str).unwrap_or("exact_in"),
+                    u64_field("amount")?,
+                    u64_field("threshold")?,
+                )?,
+                market_data: decode_market_data(event.get("market_data").and_then(Value::as_str).unwrap_or(""))
+                    .with_context(|| format!("Swap {}", index))?,
+            })
+        })
+        .collect()
+}
+
+// Parsed from the file extension, `.csv` or `.json`
+pub fn load_events(path: &Path) -> Result<Vec<SwapEvent>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => parse_events_csv(&content),
+        Some("json") => parse_events_json(&serde_json::from_str(&content).with_context(|| format!("Invalid JSON in {}", path.display()))?),
+        _ => Err(anyhow!("Unknown format of {}, expected .csv or .json", path.display())),
+    }
+}
+
+fn decode_market_data(market_data: &str) -> Result<Option<Vec<u8>>> {
+    if market_data.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(general_purpose::STANDARD.decode(market_data).context("Invalid base64 market data")?))
+}
+
+// Swap a strategy wants to run on a market
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Order {
+    pub market: Pubkey,
+    pub parameters: SwapParameters,
+}
+
+// Holdings of the strategy on a market, token 1 amounts include the fees
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub tokens: u64,
+    pub spent: u64,
+    pub received: u64,
+}
+
+pub trait Strategy {
+    // Called after every historical swap, `fill` being None when the swap reverts against the simulated state,
+    // e.g. as earlier orders moved the price past its threshold
+    // The orders returned run right after it, ahead of the next historical swap
+    fn on_swap(&mut self, event: &SwapEvent, fill: Option<&Fill>, backtest: &Backtest) -> Vec<Order>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrategyFill {
+    pub slot: u64,
+    pub market: Pubkey,
+    pub fill: Fill,
+    // How much worse than the market price before the order the average price got, fees included
+    pub slippage_bps: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RejectedOrder {
+    pub slot: u64,
+    pub order: Order,
+    pub error: String,
+}
+
+// PnL and drawdown are in token 1, so they only add up across markets sharing it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BacktestReport {
+    pub swaps: usize,
+    // Historical swaps failing against the simulated state
+    pub reverted: usize,
+    pub fills: Vec<StrategyFill>,
+    pub rejected: Vec<RejectedOrder>,
+    pub positions: HashMap<Pubkey, Position>,
+    // Token 1 received minus spent, plus what selling the remaining positions would return
+    pub pnl: i128,
+    // Largest fall of the PnL from a previous high, sampled after each historical swap and the orders following it
+    pub max_drawdown: u128,
+}
+
+impl BacktestReport {
+    pub fn to_json(&self) -> Value {
+        let mut positions = self.positions.iter().collect::<Vec<_>>();
+        positions.sort_by_key(|(market, _)| **market);
+
+        json!({
+            "swaps": self.swaps,
+            "reverted": self.reverted,
+            "pnl": self.pnl.to_string(),
+            "max_drawdown": self.max_drawdown.to_string(),
+            "fills": self.fills.iter().map(|fill| json!({
+                "slot": fill.slot,
+                "market": fill.market.to_string(),
+                "side": fill.fill.parameters.side(),
+                "mode": fill.fill.parameters.mode(),
+                "amount_in": fill.fill.quote.amount_in,
+                "amount_out": fill.fill.quote.amount_out,
+                "fee_amount_token_1": fill.fill.quote.fee_amount_token_1,
+                "price_before": sqrt_price_x96_to_price(fill.fill.sqrt_price_before),
+                "price_after": sqrt_price_x96_to_price(fill.fill.sqrt_price_after),
+                "slippage_bps": fill.slippage_bps,
+            })).collect::<Vec<_>>(),
+            "rejected": self.rejected.iter().map(|rejected| json!({
+                "slot": rejected.slot,
+                "market": rejected.order.market.to_string(),
+                "side": rejected.order.parameters.side(),
+                "amount": rejected.order.parameters.amount(),
+                "error": rejected.error,
+            })).collect::<Vec<_>>(),
+            "positions": positions.iter().map(|(market, position)| json!({
+                "market": market.to_string(),
+                "tokens": position.tokens,
+                "spent": position.spent,
+                "received": position.received,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+// Market states and strategy positions while replaying a stream
+#[derive(Clone, Debug, Default)]
+pub struct Backtest {
+    states: HashMap<Pubkey, MarketState>,
+    report: BacktestReport,
+    peak_pnl: i128,
+}
+
+impl Backtest {
+    pub fn state(&self, market: &Pubkey) -> Option<&MarketState> {
+        self.states.get(market)
+    }
+
+    pub fn position(&self, market: &Pubkey) -> Position {
+        self.report.positions.get(market).cloned().unwrap_or_default()
+    }
+
+    // Token 1 returned by selling the whole position at once, 0 when the curve can't take it
+    pub fn liquidation_value(&self, market: &Pubkey) -> u64 {
+        let position = self.position(market);
+        match self.states.get(market) {
+            Some(state) if position.tokens > 0 => state
+                .quote(&SwapParameters::SellExactIn(position.tokens, 0))
+                .map_or(0, |quote| quote.amount_out),
+            _ => 0,
+        }
+    }
+
+    pub fn pnl(&self) -> i128 {
+        self.report
+            .positions
+            .iter()
+            .map(|(market, position)| {
+                i128::from(position.received) - i128::from(position.spent) + i128::from(self.liquidation_value(market))
+            })
+            .sum()
+    }
+
+    fn apply_order(&mut self, slot: u64, order: Order) {
+        let position = self.position(&order.market);
+        let result = match self.states.get_mut(&order.market) {
+            None => Err(anyhow!("Unknown market {}", order.market)),
+            // Sells are limited to what the strategy bought
+            Some(state) => match state.quote(&order.parameters) {
+                Ok(quote) if order.parameters.zero_for_one() && quote.amount_in > position.tokens => Err(anyhow!(
+                    "InsufficientBalance: {} tokens held for {}",
+                    position.tokens,
+                    quote.amount_in
+                )),
+                _ => state.apply(&order.parameters),
+            },
+        };
+
+        match result {
+            Ok(fill) => {
+                let quote = &fill.quote;
+                let position = self.report.positions.entry(order.market).or_default();
+                let spot_price = sqrt_price_x96_to_price(fill.sqrt_price_before);
+                let slippage = if order.parameters.zero_for_one() {
+                    position.tokens -= quote.amount_in;
+                    position.received = position.received.saturating_add(quote.amount_out);
+                    1.0 - quote.amount_out as f64 / quote.amount_in as f64 / spot_price
+                } else {
+                    position.tokens = position.tokens.saturating_add(quote.amount_out);
+                    position.spent = position.spent.saturating_add(quote.amount_in);
+                    quote.amount_in as f64 / quote.amount_out as f64 / spot_price - 1.0
+                };
+
+                self.report.fills.push(StrategyFill {
+                    slot,
+                    market: order.market,
+                    fill,
+                    slippage_bps: slippage * MAX_BPS as f64,
+                });
+            }
+            Err(err) => self.report.rejected.push(RejectedOrder {
+                slot,
+                order,
+                error: err.to_string(),
+            }),
+        }
+    }
+
+    fn update_drawdown(&mut self) {
+        let pnl = self.pnl();
+        self.peak_pnl = self.peak_pnl.max(pnl);
+        self.report.max_drawdown = self.report.max_drawdown.max((self.peak_pnl - pnl) as u128);
+    }
+}
+
+// Replays the swaps in order through the local quote, feeding each one to the strategy
+pub fn run_backtest(events: &[SwapEvent], strategy: &mut impl Strategy) -> Result<BacktestReport> {
+    let mut backtest = Backtest::default();
+
+    for event in events {
+        let state = match backtest.states.entry(event.market) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let market_data = event.market_data.as_ref().ok_or(anyhow!(
+                    "MissingMarketData: first swap of market {} in {} has no market data",
+                    event.market,
+                    event.signature
+                ))?;
+                let market = Market::from_bytes(market_data)
+                    .with_context(|| format!("Failed to decode market of {}", event.signature))?;
+                entry.insert(MarketState::new(market)?)
+            }
+        };
+        let fill = state.apply(&event.parameters).ok();
+        backtest.report.swaps += 1;
+        if fill.is_none() {
+            backtest.report.reverted += 1;
+        }
+
+        for order in strategy.on_swap(event, fill.as_ref(), &backtest) {
+            backtest.apply_order(event.slot, order);
+        }
+        backtest.update_drawdown();
+    }
+
+    backtest.report.pnl = backtest.pnl();
+    Ok(backtest.report)
+}
+
+// Buys a fixed amount of token 1 on the first swap seen on each market, then sells the whole position once
+// its liquidation value is up `take_profit_bps` or down `stop_loss_bps` from what it cost
+#[derive(Clone, Debug, Default)]
+pub struct SnipeAndExit {
+    pub buy_amount: u64,
+    pub take_profit_bps: u64,
+    pub stop_loss_bps: u64,
+    entered: HashSet<Pubkey>,
+}
+
+impl SnipeAndExit {
+    pub fn new(buy_amount: u64, take_profit_bps: u64, stop_loss_bps: u64) -> Self {
+        Self {
+            buy_amount,
+            take_profit_bps,
+            stop_loss_bps,
+            entered: HashSet::new(),
+        }
+    }
+}
+
+impl Strategy for SnipeAndExit {
+    fn on_swap(&mut self, event: &SwapEvent, _fill: Option<&Fill>, backtest: &Backtest) -> Vec<Order> {
+        if self.entered.insert(event.market) {
+            return vec![Order {
+                market: event.market,
+                parameters: SwapParameters::BuyExactIn(self.buy_amount, 0),
+            }];
+        }
+
+        let position = backtest.position(&event.market);
+        if position.tokens == 0 {
+            return vec![];
+        }
+
+        let value = u128::from(backtest.liquidation_value(&event.market)) * u128::from(MAX_BPS);
+        let cost = u128::from(position.spent);
+        if value >= cost * u128::from(MAX_BPS + self.take_profit_bps)
+            || value <= cost * u128::from(MAX_BPS.saturating_sub(self.stop_loss_bps))
+        {
+            return vec![Order {
+                market: event.market,
+                parameters: SwapParameters::SellExactIn(position.tokens, 0),
+            }];
+        }
+
+        vec![]
+    }
+}
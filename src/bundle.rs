@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Context, Result};
+
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::{json, Value};
+use solana_sdk::{
+    hash::Hash,
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+
+use crate::market::Market;
+use crate::state::MarketState;
+use crate::swap::{build_swap_instructions, QuotedSwap, SwapAccounts, SwapOptions, SwapParameters};
+use crate::token::is_native_mint;
+
+pub const JITO_TIP_ACCOUNTS: [&str; 8] = [
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+];
+pub const DEFAULT_BLOCK_ENGINE_URL: &str = "https://mainnet.block-engine.jito.wtf/api/v1/bundles";
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+// What landing the bundle costs on top of the swaps, the compute unit limit is set on both transactions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BundleCosts {
+    pub tip_lamports: u64,
+    pub compute_unit_limit: u32,
+    pub priority_fee_micro_lamports: u64,
+}
+
+impl BundleCosts {
+    // Base fee of the single signature plus the priority fee of one transaction
+    pub fn transaction_fee(&self) -> u64 {
+        let priority_fee = (u128::from(self.compute_unit_limit) * u128::from(self.priority_fee_micro_lamports)).div_ceil(1_000_000);
+
+        LAMPORTS_PER_SIGNATURE.saturating_add(u64::try_from(priority_fee).unwrap_or(u64::MAX))
+    }
+
+    pub fn total(&self) -> u64 {
+        self.tip_lamports.saturating_add(self.transaction_fee().saturating_mul(2))
+    }
+
+    pub fn swap_options(&self) -> SwapOptions {
+        SwapOptions {
+            compute_unit_limit: Some(self.compute_unit_limit),
+            priority_fee_micro_lamports: Some(self.priority_fee_micro_lamports),
+        }
+    }
+}
+
+// A buy followed by the sell of what it's guaranteed to return, landing back to back in one bundle
+// Nets are in lamports, after the swaps and the costs of the bundle
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoundTrip {
+    pub buy: QuotedSwap,
+    pub sell: QuotedSwap,
+    pub costs: BundleCosts,
+    // When both swaps fill as quoted, tokens bought above the buy threshold staying in the wallet uncounted
+    pub expected_net: i128,
+    // When both swaps only meet their thresholds
+    pub worst_net: i128,
+}
+
+// The sell is quoted on the market as the buy leaves it, which nothing can come in between within a bundle
+// Refuses round trips whose worst case nets less than `min_net_lamports`, which is negative to accept paying for it
+pub fn plan_round_trip(
+    market: &Market,
+    amount: u64,
+    slippage_bps: u64,
+    costs: &BundleCosts,
+    min_net_lamports: i64,
+) -> Result<RoundTrip> {
+    if !is_native_mint(&market.token_mint1) {
+        return Err(anyhow!("UnsupportedQuoteToken: bundle costs are in SOL, market is quoted in {}", market.token_mint1));
+    }
+
+    let mut state = MarketState::new(market.clone())?;
+    let buy = SwapParameters::BuyExactIn(amount, 0);
+    let buy = buy.with_slippage(&state.quote(&buy)?, slippage_bps)?;
+    let buy_quote = state.apply(&buy)?.quote;
+
+    let sell = SwapParameters::SellExactIn(buy.threshold(), 0);
+    let sell_quote = state.quote(&sell)?;
+    let sell = sell.with_slippage(&sell_quote, slippage_bps)?;
+
+    let costs_total = i128::from(costs.total());
+    let expected_net = i128::from(sell_quote.amount_out) - i128::from(buy_quote.amount_in) - costs_total;
+    let worst_net = i128::from(sell.threshold()) - i128::from(buy.amount()) - costs_total;
+    if worst_net < i128::from(min_net_lamports) {
+        return Err(anyhow!(
+            "UnprofitableBundle: nets {} lamports at worst, {} expected, below {}",
+            worst_net,
+            expected_net,
+            min_net_lamports
+        ));
+    }
+
+    Ok(RoundTrip {
+        buy: QuotedSwap { parameters: buy, quote: buy_quote },
+        sell: QuotedSwap { parameters: sell, quote: sell_quote },
+        costs: costs.clone(),
+        expected_net,
+        worst_net,
+    })
+}
+
+// Both transactions signed by the wallet, the tip going last as the block engine expects
+pub fn build_round_trip_bundle(
+    accounts: &SwapAccounts,
+    round_trip: &RoundTrip,
+    wallet: &Keypair,
+    tip_account: &Pubkey,
+    recent_blockhash: Hash,
+) -> Vec<Transaction> {
+    let user = wallet.pubkey();
+    let options = round_trip.costs.swap_options();
+
+    let buy = build_swap_instructions(accounts, &user, &round_trip.buy.parameters, &options);
+    let mut sell = build_swap_instructions(accounts, &user, &round_trip.sell.parameters, &options);
+    sell.push(system_instruction::transfer(&user, tip_account, round_trip.costs.tip_lamports));
+
+    [buy, sell]
+        .iter()
+        .map(|instructions| Transaction::new(&[wallet], Message::new(instructions, Some(&user)), recent_blockhash))
+        .collect()
+}
+
+// Returns the bundle id
+pub async fn send_bundle(client: &reqwest::Client, block_engine_url: &str, transactions: &[Transaction]) -> Result<String> {
+    let encoded = transactions
+        .iter()
+        .map(|transaction| Ok(general_purpose::STANDARD.encode(bincode::serialize(transaction)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "sendBundle",
+        "params": [encoded, { "encoding": "base64" }],
+    });
+
+    let response = client
+        .post(block_engine_url)
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {}", block_engine_url))?;
+    let response = serde_json::from_str::<Value>(&response.text().await?).context("Invalid block engine response")?;
+
+    if let Some(error) = response.get("error") {
+        return Err(anyhow!("Bundle rejected: {}", error));
+    }
+    response
+        .get("result")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or(anyhow!("Invalid block engine response: {}", response))
+}
@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    pubkey::Pubkey,
+    system_program,
+    transaction::Transaction,
+};
+
+use crate::market::{Market, MarketSettings, TOKENMILL_PROGRAM};
+use crate::swap::{find_event_authority, SwapOptions};
+use crate::swap_math::MAX_FEE_U128;
+use crate::token::get_associated_token_address;
+
+pub const CREATE_MARKET_DISCRIMINATOR: [u8; 8] = [103, 226, 97, 235, 200, 188, 251, 254];
+pub const CREATE_MARKET_WITH_SPL_DISCRIMINATOR: [u8; 8] = [75, 117, 88, 13, 142, 106, 70, 82];
+pub const METADATA_PROGRAM: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+
+// Token 2022 mints carry their metadata in the mint extensions, SPL mints in a Metaplex metadata account
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MintStandard {
+    Token2022,
+    Spl,
+}
+
+impl MintStandard {
+    pub fn token_program(&self) -> Pubkey {
+        match self {
+            MintStandard::Token2022 => spl_token_2022::id(),
+            MintStandard::Spl => spl_token::id(),
+        }
+    }
+}
+
+// Arguments of the create market instructions, in their serialization order
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct CreateMarketArgs {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub settings: MarketSettings,
+    pub swap_authority: Option<Pubkey>,
+}
+
+// Token 0 is a new mint created and initialized by the program, which mints the whole `max_supply` into the market
+// reserve, so its keypair has to sign the transaction along with the creator
+#[derive(Clone, Debug)]
+pub struct CreateMarketAccounts {
+    pub config: Pubkey,
+    pub market: Pubkey,
+    pub token_mint0: Pubkey,
+    pub token_mint1: Pubkey,
+    pub reserve0: Pubkey,
+    pub reserve1: Pubkey,
+    pub token_program0: Pubkey,
+    pub token_program1: Pubkey,
+    // Only for SPL mints
+    pub metadata: Option<Pubkey>,
+}
+
+impl CreateMarketAccounts {
+    pub fn new(
+        config: &Pubkey,
+        token_mint0: &Pubkey,
+        token_mint1: &Pubkey,
+        standard: MintStandard,
+        token_program1: &Pubkey,
+    ) -> Self {
+        let market = Market::find_pda(token_mint0).0;
+        let token_program0 = standard.token_program();
+
+        Self {
+            config: *config,
+            market,
+            token_mint0: *token_mint0,
+            token_mint1: *token_mint1,
+            reserve0: get_associated_token_address(&market, token_mint0, &token_program0),
+            reserve1: get_associated_token_address(&market, token_mint1, token_program1),
+            token_program0,
+            token_program1: *token_program1,
+            metadata: match standard {
+                MintStandard::Token2022 => None,
+                MintStandard::Spl => Some(find_metadata_account(token_mint0)),
+            },
+        }
+    }
+}
+
+pub fn find_metadata_account(mint: &Pubkey) -> Pubkey {
+    let metadata_program = Pubkey::from_str_const(METADATA_PROGRAM);
+
+    Pubkey::find_program_address(&[b"metadata", metadata_program.as_ref(), mint.as_ref()], &metadata_program).0
+}
+
+// Same checks as the program, so that a bad curve fails before paying for a transaction
+pub fn validate_create_market_args(args: &CreateMarketArgs) -> Result<()> {
+    let settings = &args.settings;
+
+    if settings.sqrt_price_a_x96 == 0 || settings.sqrt_price_a_x96 >= settings.sqrt_price_b_x96 {
+        return Err(anyhow!(
+            "InvalidSqrtPrices: {} {}",
+            settings.sqrt_price_a_x96,
+            settings.sqrt_price_b_x96
+        ));
+    }
+    if settings.liquidity_a == 0 || settings.liquidity_b == 0 {
+        return Err(anyhow!("InvalidLiquidity: {} {}", settings.liquidity_a, settings.liquidity_b));
+    }
+    if settings.max_supply == 0 {
+        return Err(anyhow!("InvalidMaxSupply: {}", settings.max_supply));
+    }
+    if u128::from(settings.fee) >= MAX_FEE_U128 {
+        return Err(anyhow!("InvalidFee: {}", settings.fee));
+    }
+
+    Ok(())
+}
+
+pub fn build_create_market_instruction(
+    accounts: &CreateMarketAccounts,
+    creator: &Pubkey,
+    args: &CreateMarketArgs,
+) -> Instruction {
+    let program_id = Pubkey::from_str_const(TOKENMILL_PROGRAM);
+
+    let mut instruction_accounts = vec![
+        AccountMeta::new_readonly(accounts.config, false), //#0 config
+        AccountMeta::new(accounts.market, false), //#1 market
+        AccountMeta::new(accounts.token_mint0, true), //#2 token mint 0, created by the program
+        AccountMeta::new_readonly(accounts.token_mint1, false), //#3 token mint 1
+        AccountMeta::new(accounts.reserve0, false), //#4 market reserve 0
+        AccountMeta::new(accounts.reserve1, false), //#5 market reserve 1
+        AccountMeta::new(*creator, true), //#6 creator, paying for the accounts
+    ];
+    let discriminator = match accounts.metadata {
+        Some(metadata) => {
+            instruction_accounts.push(AccountMeta::new(metadata, false)); //#7 metadata
+            instruction_accounts.push(AccountMeta::new_readonly(Pubkey::from_str_const(METADATA_PROGRAM), false)); //#8 metadata program
+            CREATE_MARKET_WITH_SPL_DISCRIMINATOR
+        }
+        None => CREATE_MARKET_DISCRIMINATOR,
+    };
+    instruction_accounts.extend([
+        AccountMeta::new_readonly(accounts.token_program0, false), // token program 0
+        AccountMeta::new_readonly(accounts.token_program1, false), // token program 1
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false), // associated token program
+        AccountMeta::new_readonly(system_program::id(), false), // system program
+        AccountMeta::new_readonly(find_event_authority(), false), // event authority
+        AccountMeta::new_readonly(program_id, false), // program
+    ]);
+
+    let mut data = discriminator.to_vec();
+    // Borsh serialization into a vec can't fail
+    args.serialize(&mut data).unwrap();
+
+    Instruction {
+        program_id,
+        accounts: instruction_accounts,
+        data,
+    }
+}
+
+// Compute budget followed by the market creation, the program creating the mint and both reserves itself
+pub fn build_create_market_instructions(
+    accounts: &CreateMarketAccounts,
+    creator: &Pubkey,
+    args: &CreateMarketArgs,
+    options: &SwapOptions,
+) -> Result<Vec<Instruction>> {
+    validate_create_market_args(args)?;
+    let mut instructions = vec![];
+
+    if let Some(compute_unit_limit) = options.compute_unit_limit {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit));
+    }
+    if let Some(priority_fee) = options.priority_fee_micro_lamports {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_price(priority_fee));
+    }
+    instructions.push(build_create_market_instruction(accounts, creator, args));
+
+    Ok(instructions)
+}
+
+// Needs to be signed by the creator and the token 0 mint keypair
+pub fn build_create_market_transaction(
+    accounts: &CreateMarketAccounts,
+    creator: &Pubkey,
+    args: &CreateMarketArgs,
+    options: &SwapOptions,
+) -> Result<Transaction> {
+    let instructions = build_create_market_instructions(accounts, creator, args, options)?;
+    let message = Message::new(&instructions, Some(creator));
+
+    Ok(Transaction::new_unsigned(message))
+}
@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Result};
+use ruint::aliases::U512;
+use serde_json::{json, Value};
+
+use crate::market::Market;
+use crate::price::sqrt_price_x96_to_price;
+use crate::quote::{PreparedMarket, Quote};
+use crate::swap::{default_sqrt_price_limit, MAX_BPS};
+
+// Level of a synthetic order book, amounts are cumulative from the current market price
+// `sqrt_price_x96` is where the swap stops filling the curve, for sells the swap converting the fee to token 1
+// moves the market price a bit further
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepthLevel {
+    pub zero_for_one: bool,
+    // Price move in bps or amount in, depending on how the ladder was built
+    pub step: u64,
+    pub sqrt_price_x96: u128,
+    // Fee included
+    pub amount_in: u64,
+    pub amount_out: u64,
+    // In the input token
+    pub fee_amount: u64,
+}
+
+impl DepthLevel {
+    fn new(zero_for_one: bool, step: u64, quote: &Quote) -> Self {
+        Self {
+            zero_for_one,
+            step,
+            sqrt_price_x96: quote.legs.last().map_or(quote.next_sqrt_price, |leg| leg.sqrt_price_end),
+            amount_in: quote.amount_in,
+            amount_out: quote.amount_out,
+            fee_amount: quote.fee_amount_token_in,
+        }
+    }
+
+    pub fn side(&self) -> &'static str {
+        if self.zero_for_one {
+            "sell"
+        } else {
+            "buy"
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "side": self.side(),
+            "step": self.step,
+            "sqrt_price_x96": self.sqrt_price_x96.to_string(),
+            "price": sqrt_price_x96_to_price(self.sqrt_price_x96),
+            "amount_in": self.amount_in,
+            "amount_out": self.amount_out,
+            "fee_amount": self.fee_amount,
+        })
+    }
+}
+
+pub const DEPTH_CSV_HEADER: &str = "side,step,sqrt_price_x96,price,amount_in,amount_out,fee_amount";
+
+pub fn depth_to_csv(levels: &[DepthLevel]) -> String {
+    let mut csv = String::from(DEPTH_CSV_HEADER) + "\n";
+    for level in levels {
+        csv += &format!(
+            "{},{},{},{},{},{},{}\n",
+            level.side(),
+            level.step,
+            level.sqrt_price_x96,
+            sqrt_price_x96_to_price(level.sqrt_price_x96),
+            level.amount_in,
+            level.amount_out,
+            level.fee_amount
+        );
+    }
+
+    csv
+}
+
+// One level per price move from the current price, e.g. 100 bps is the price 1% above for buys and 1% below for sells
+// Sells are cut at the start of the curve, which ends their ladder, buys stop before the first level
+// needing more than a single swap can take
+pub fn depth_by_price(market: &Market, zero_for_one: bool, steps_bps: &[u64]) -> Vec<DepthLevel> {
+    let prepared = PreparedMarket::new(&market.settings);
+    let mut steps_bps = steps_bps.to_vec();
+    steps_bps.sort_unstable();
+    steps_bps.dedup();
+
+    let mut levels = vec![];
+    for step in steps_bps {
+        let Some(target_sqrt_price) = sqrt_price_after_move(market.sqrt_price_x96, zero_for_one, step) else {
+            break;
+        };
+        let sqrt_price_limit = if zero_for_one {
+            target_sqrt_price.max(market.settings.sqrt_price_a_x96)
+        } else {
+            target_sqrt_price
+        };
+
+        // Exact in swap large enough to always stop at the limit
+        let Ok(quote) = prepared.quote_with_legs(market.sqrt_price_x96, zero_for_one, i64::MAX, sqrt_price_limit) else {
+            break;
+        };
+        let level = DepthLevel::new(zero_for_one, step, &quote);
+        if level.sqrt_price_x96 != sqrt_price_limit {
+            break;
+        }
+        levels.push(level);
+
+        if sqrt_price_limit != target_sqrt_price {
+            break;
+        }
+    }
+
+    levels
+}
+
+// One level per exact in swap size, in token 1 for buys and in token 0 for sells
+// The ladder stops after the first sell emptying the curve, or before the first buy too large to be quoted
+pub fn depth_by_size(market: &Market, zero_for_one: bool, sizes: &[u64]) -> Result<Vec<DepthLevel>> {
+    let prepared = PreparedMarket::new(&market.settings);
+    let sqrt_price_limit = default_sqrt_price_limit(market, zero_for_one);
+    let mut sizes = sizes.to_vec();
+    sizes.sort_unstable();
+    sizes.dedup();
+
+    let mut levels = vec![];
+    for size in sizes {
+        let delta_amount = i64::try_from(size).map_err(|_| anyhow!("InvalidSize: {}", size))?;
+        let Ok(quote) = prepared.quote_with_legs(market.sqrt_price_x96, zero_for_one, delta_amount, sqrt_price_limit) else {
+            break;
+        };
+        levels.push(DepthLevel::new(zero_for_one, size, &quote));
+
+        if quote.hit_sqrt_price_limit {
+            break;
+        }
+    }
+
+    Ok(levels)
+}
+
+// Sqrt price of the price moved by `bps`, rounded so that the move is at least `bps`
+fn sqrt_price_after_move(sqrt_price_x96: u128, zero_for_one: bool, bps: u64) -> Option<u128> {
+    let factor = if zero_for_one {
+        MAX_BPS.saturating_sub(bps)
+    } else {
+        MAX_BPS.checked_add(bps)?
+    };
+    let sqrt_price = U512::from(sqrt_price_x96);
+    let price = sqrt_price * sqrt_price * U512::from(factor) / U512::from(MAX_BPS);
+
+    let mut target = price.root(2);
+    if !zero_for_one && target * target < price {
+        target += U512::from(1u8);
+    }
+
+    u128::try_from(target).ok()
+}
@@ -0,0 +1,246 @@
+use anyhow::{anyhow, Result};
+use ruint::aliases::U256;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::market::{Market, MarketSettings, MARKET_DISCRIMINATOR};
+use crate::math::mul_div;
+use crate::price::{price_to_sqrt_price_x96, sqrt_price_x96_to_price, ui_price_to_price};
+use crate::quote::quote;
+use crate::supply::{curve_end_sqrt_price, quote_raised, tokens_sold};
+use crate::swap_math::{get_amount_0, get_next_sqrt_ratio_from_amount_0, MAX_FEE_U128, SQRT_PRICE_SHIFT};
+
+// Relative gap tolerated between the designed curve and the one quoted from the derived settings,
+// liquidities being rounded to integers
+const MAX_RELATIVE_ERROR: f64 = 1e-6;
+// Amounts are also off by a few raw units of rounding per phase
+const MAX_ROUNDING_ERROR: f64 = 2.0;
+
+// Business parameters of a curve, prices are in whole token 1 per whole token 0 and supplies in raw token 0 units
+#[derive(Clone, Debug, PartialEq)]
+pub struct CurveDesign {
+    pub start_price: f64,
+    pub boundary_price: f64,
+    pub end_price: f64,
+    pub supply_a: u64,
+    pub supply_b: u64,
+    pub decimals0: u8,
+    pub decimals1: u8,
+    pub fee: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CurvePoint {
+    pub sold: u64,
+    pub sqrt_price_x96: u128,
+    // Token 1 raised once `sold` tokens are bought, fees excluded
+    pub raised: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DesignedCurve {
+    pub settings: MarketSettings,
+    // Price at which the whole supply is sold, not part of the settings as the program derives it from `max_supply`
+    pub end_sqrt_price_x96: u128,
+    pub raise_a: u64,
+    pub total_raise: u64,
+    pub curve: Vec<CurvePoint>,
+}
+
+// Inverts `get_amount_0` over each phase to get the liquidities selling the supplies between the prices
+// Liquidities are rounded down, so that each phase never sells more than its supply
+// `points` is the number of intervals of the price curve sampled along the supply
+pub fn design_curve(design: &CurveDesign, points: usize) -> Result<DesignedCurve> {
+    if !(0.0 < design.start_price && design.start_price < design.boundary_price && design.boundary_price < design.end_price) {
+        return Err(anyhow!(
+            "InvalidCurve: prices must be increasing, got {} {} {}",
+            design.start_price,
+            design.boundary_price,
+            design.end_price
+        ));
+    }
+    if design.supply_a == 0 || design.supply_b == 0 {
+        return Err(anyhow!("InvalidCurve: both phases need a supply"));
+    }
+    if u128::from(design.fee) >= MAX_FEE_U128 {
+        return Err(anyhow!("InvalidFee: {}", design.fee));
+    }
+    let max_supply = design
+        .supply_a
+        .checked_add(design.supply_b)
+        .ok_or(anyhow!("InvalidCurve: supply overflows"))?;
+
+    let sqrt_price = |price: f64| {
+        price_to_sqrt_price_x96(ui_price_to_price(price, design.decimals0, design.decimals1))
+            .ok_or(anyhow!("InvalidPrice: {}", price))
+    };
+    let sqrt_price_a_x96 = sqrt_price(design.start_price)?;
+    let sqrt_price_b_x96 = sqrt_price(design.boundary_price)?;
+    let end_sqrt_price_x96 = sqrt_price(design.end_price)?;
+    if !(0 < sqrt_price_a_x96 && sqrt_price_a_x96 < sqrt_price_b_x96 && sqrt_price_b_x96 < end_sqrt_price_x96) {
+        return Err(anyhow!("InvalidCurve: prices are too close to tell apart"));
+    }
+
+    let liquidity_a = liquidity_for_amount_0(sqrt_price_a_x96, sqrt_price_b_x96, design.supply_a)?;
+    // The program ends the curve once phase B sold what phase A left of `max_supply`, including what phase A
+    // falls short of its supply
+    let sold_a = get_amount_0(sqrt_price_a_x96, sqrt_price_b_x96, liquidity_a, false)?;
+    let supply_b = u64::try_from(u128::from(max_supply) - sold_a)?;
+    let settings = MarketSettings {
+        max_supply,
+        sqrt_price_a_x96,
+        sqrt_price_b_x96,
+        liquidity_a,
+        liquidity_b: liquidity_for_amount_0(sqrt_price_b_x96, end_sqrt_price_x96, supply_b)?,
+        fee: design.fee,
+    };
+    validate_settings(&settings, end_sqrt_price_x96, design.supply_a)?;
+
+    let curve = (0..=points)
+        .map(|point| {
+            let sold = (u128::from(max_supply) * point as u128 / points.max(1) as u128) as u64;
+            let sqrt_price_x96 = sqrt_price_after_selling(&settings, sold)?;
+
+            Ok(CurvePoint {
+                sold,
+                sqrt_price_x96,
+                raised: quote_raised(&settings, sqrt_price_x96)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(DesignedCurve {
+        end_sqrt_price_x96,
+        raise_a: quote_raised(&settings, sqrt_price_b_x96)?,
+        total_raise: quote_raised(&settings, end_sqrt_price_x96)?,
+        curve,
+        settings,
+    })
+}
+
+// Buys out the curve from its start with `quote` and checks it ends at the designed prices for the designed raises
+// `supply_a` is the designed supply of phase A, the rest of `max_supply` being the one of phase B
+pub fn validate_settings(settings: &MarketSettings, end_sqrt_price_x96: u128, supply_a: u64) -> Result<()> {
+    let market = Market {
+        discriminator: MARKET_DISCRIMINATOR,
+        config: Pubkey::default(),
+        creator: Pubkey::default(),
+        swap_authority: None,
+        token_mint0: Pubkey::default(),
+        token_mint1: Pubkey::default(),
+        reserve0: Pubkey::default(),
+        reserve1: Pubkey::default(),
+        fee_reserve: None,
+        fee_reserve_last_update: 0,
+        settings: settings.clone(),
+        sqrt_price_x96: settings.sqrt_price_a_x96,
+        bump: [0],
+    };
+    let is_close_price = |actual: u128, expected: u128| {
+        let (actual, expected) = (sqrt_price_x96_to_price(actual), sqrt_price_x96_to_price(expected));
+        (actual - expected).abs() <= expected * MAX_RELATIVE_ERROR
+    };
+    let amount_tolerance = |expected: f64| (expected * MAX_RELATIVE_ERROR).max(MAX_ROUNDING_ERROR);
+
+    let supply_b = settings
+        .max_supply
+        .checked_sub(supply_a)
+        .ok_or(anyhow!("InvalidCurve: phase A supply above the max supply"))?;
+    // Liquidities being rounded down, each phase sells its supply short by up to the share of one unit of liquidity
+    let shortfall = |supply: u64, liquidity: u128| supply as f64 / liquidity as f64 + 1.0;
+    let shortfall_a = shortfall(supply_a, settings.liquidity_a);
+    let shortfall_b = shortfall(supply_b, settings.liquidity_b);
+
+    // The program derives the end of the curve from `max_supply`, it has to land on the designed end price
+    // or, phase B selling its supply short, past it by the price of what phase B falls short of
+    let curve_end_sqrt_price_x96 = curve_end_sqrt_price(settings)?;
+    let max_curve_end_sqrt_price_x96 =
+        get_next_sqrt_ratio_from_amount_0(end_sqrt_price_x96, settings.liquidity_b, -(shortfall_b.ceil() as i64))?;
+    if !is_close_price(curve_end_sqrt_price_x96, end_sqrt_price_x96)
+        && !(end_sqrt_price_x96..=max_curve_end_sqrt_price_x96).contains(&curve_end_sqrt_price_x96)
+    {
+        return Err(anyhow!(
+            "CurveMismatch: the curve sells out at {}, designed to end at {}",
+            curve_end_sqrt_price_x96,
+            end_sqrt_price_x96
+        ));
+    }
+
+    let sold_a = tokens_sold(settings, settings.sqrt_price_b_x96)?;
+    let sold = tokens_sold(settings, end_sqrt_price_x96)?;
+    if sold_a > supply_a || (supply_a - sold_a) as f64 > shortfall_a {
+        return Err(anyhow!("CurveMismatch: phase A sells {} tokens, designed to sell {}", sold_a, supply_a));
+    }
+    if sold > settings.max_supply || (settings.max_supply - sold) as f64 > shortfall_a + shortfall_b {
+        return Err(anyhow!(
+            "CurveMismatch: {} tokens sold at the end price, designed to sell {}",
+            sold,
+            settings.max_supply
+        ));
+    }
+
+    let raise_a = designed_raise(settings.sqrt_price_a_x96, settings.sqrt_price_b_x96, supply_a);
+    let total_raise = raise_a + designed_raise(settings.sqrt_price_b_x96, end_sqrt_price_x96, supply_b);
+
+    // The tokens a phase falls short of are the ones that would have been sold last, at most at its end price
+    for (amount, expected_sqrt_price, expected_raise, missing) in [
+        (sold_a, settings.sqrt_price_b_x96, raise_a, shortfall_a),
+        (sold, end_sqrt_price_x96, total_raise, shortfall_a + shortfall_b),
+    ] {
+        let delta_amount = -i64::try_from(amount).map_err(|_| anyhow!("InvalidCurve: supply too large to be bought at once"))?;
+        let quote = quote(&market, false, delta_amount, u128::MAX)?;
+        let raised = quote.amount_in - quote.fee_amount_token_in;
+        let raise_gap = expected_raise - raised as f64;
+        let max_raise_gap = missing * sqrt_price_x96_to_price(expected_sqrt_price) + amount_tolerance(expected_raise);
+
+        if quote.amount_out != amount
+            || !is_close_price(quote.next_sqrt_price, expected_sqrt_price)
+            || !(-amount_tolerance(expected_raise)..=max_raise_gap).contains(&raise_gap)
+        {
+            return Err(anyhow!(
+                "CurveMismatch: buying {} tokens ends at {} for {}, designed to end at {} for {}",
+                amount,
+                quote.next_sqrt_price,
+                raised,
+                expected_sqrt_price,
+                expected_raise
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// amount_0 = liquidity * 2^96 * (sqrt_price_b - sqrt_price_a) / (sqrt_price_a * sqrt_price_b)
+fn liquidity_for_amount_0(sqrt_price_a_x96: u128, sqrt_price_b_x96: u128, amount_0: u64) -> Result<u128> {
+    let liquidity = mul_div(
+        U256::from(amount_0) * U256::from(sqrt_price_a_x96),
+        U256::from(sqrt_price_b_x96),
+        U256::from(sqrt_price_b_x96 - sqrt_price_a_x96) << SQRT_PRICE_SHIFT,
+    )?;
+
+    if liquidity == 0 {
+        return Err(anyhow!("InvalidCurve: {} tokens are too few to fill a phase", amount_0));
+    }
+
+    Ok(liquidity)
+}
+
+// Token 1 raised by selling `supply` between two prices, fees excluded
+// Integrating the curve, it's the supply sold at the geometric mean of the prices
+fn designed_raise(sqrt_price_start_x96: u128, sqrt_price_end_x96: u128, supply: u64) -> f64 {
+    supply as f64 * (sqrt_price_x96_to_price(sqrt_price_start_x96) * sqrt_price_x96_to_price(sqrt_price_end_x96)).sqrt()
+}
+
+// Price once `sold` tokens have been bought from the start of the curve
+fn sqrt_price_after_selling(settings: &MarketSettings, sold: u64) -> Result<u128> {
+    let supply_a = tokens_sold(settings, settings.sqrt_price_b_x96)?;
+
+    let sqrt_price_x96 = match sold.checked_sub(supply_a) {
+        Some(sold_b) if sold_b > 0 => {
+            get_next_sqrt_ratio_from_amount_0(settings.sqrt_price_b_x96, settings.liquidity_b, -(sold_b as i64))?
+        }
+        _ => get_next_sqrt_ratio_from_amount_0(settings.sqrt_price_a_x96, settings.liquidity_a, -(sold as i64))?,
+    };
+
+    Ok(sqrt_price_x96)
+}
@@ -0,0 +1,163 @@
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+use thiserror::Error;
+
+use crate::market::TOKENMILL_PROGRAM;
+
+// Failures of the local swap math, named after the matching `TokenMillV2Error` variants of the program
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapMathError {
+    #[error("AmountOverflow")]
+    AmountOverflow,
+    #[error("AmountInOverflow")]
+    AmountInOverflow,
+    #[error("AmountOutOverflow")]
+    AmountOutOverflow,
+    #[error("FeeAmountOverflow")]
+    FeeAmountOverflow,
+    #[error("DeltaAmountOverflow")]
+    DeltaAmountOverflow,
+    #[error("LiquidityOverflow0")]
+    LiquidityOverflow0,
+    #[error("LiquidityOverflow1")]
+    LiquidityOverflow1,
+    #[error("PriceOverflow")]
+    PriceOverflow,
+    #[error("DivisionByZero")]
+    DivisionByZero,
+}
+
+// Custom errors of the program, numbered from 6000 as Anchor does
+// Only the codes that could be checked are listed, the IDL of the program not being at hand: 0x1771 when a swap
+// goes past its threshold. Other codes are named from the logs of the failure, see `ProgramError`
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum TokenMillError {
+    #[error("SlippageExceeded: swap amounts went past their threshold")]
+    SlippageExceeded = 6001,
+}
+
+impl TokenMillError {
+    pub const ALL: [Self; 1] = [Self::SlippageExceeded];
+
+    pub fn code(&self) -> u32 {
+        *self as u32
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|err| err.code() == code)
+    }
+
+    // Custom codes are per program, so a failing instruction of another program could use the same ones
+    // Programs TokenMill calls into fail with their own codes, which are below 6000
+    pub fn from_transaction_error(err: &TransactionError) -> Option<Self> {
+        match err {
+            TransactionError::InstructionError(_, InstructionError::Custom(code)) => Self::from_code(*code),
+            _ => None,
+        }
+    }
+
+    // From the logs of a failed transaction or simulation, the line reporting the failure naming the program
+    pub fn from_logs(logs: &[String]) -> Option<Self> {
+        let prefix = format!("Program {} failed: custom program error: 0x", TOKENMILL_PROGRAM);
+
+        logs.iter()
+            .find_map(|log| log.strip_prefix(&prefix))
+            .and_then(|code| u32::from_str_radix(code, 16).ok())
+            .and_then(Self::from_code)
+    }
+}
+
+// A failure of the program as Anchor logs it, which names every custom error of the program, listed or not:
+// "AnchorError occurred. Error Code: <name>. Error Number: <code>. Error Message: <message>."
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramError {
+    pub code: u32,
+    pub name: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+impl ProgramError {
+    // The Anchor error logged last before the program failed with its code, as the programs it calls into log
+    // theirs too
+    pub fn from_logs(logs: &[String]) -> Option<Self> {
+        let prefix = format!("Program {} failed: custom program error: 0x", TOKENMILL_PROGRAM);
+        let failure = logs.iter().position(|log| log.starts_with(&prefix))?;
+        let code = u32::from_str_radix(&logs[failure][prefix.len()..], 16).ok()?;
+
+        logs[..failure]
+            .iter()
+            .rev()
+            .find_map(|log| Self::from_log(log))
+            .filter(|err| err.code == code)
+    }
+
+    fn from_log(log: &str) -> Option<Self> {
+        let (_, error) = log.strip_prefix("Program log: AnchorError ")?.split_once("Error Code: ")?;
+        let (name, error) = error.split_once(". Error Number: ")?;
+        let (code, message) = error.split_once(". Error Message: ")?;
+
+        Some(Self {
+            code: code.parse().ok()?,
+            name: name.to_string(),
+            message: message.strip_suffix('.').unwrap_or(message).to_string(),
+        })
+    }
+}
+
+// Preflight simulations return the logs of the failed transaction along with its error
+fn preflight_logs(err: &ClientError) -> Option<&[String]> {
+    match err.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+            ..
+        }) => result.logs.as_deref(),
+        _ => None,
+    }
+}
+
+// Names the program error instead of showing its hexadecimal code, when it's a known one
+pub fn describe_transaction_error(err: &TransactionError) -> String {
+    match (err, TokenMillError::from_transaction_error(err)) {
+        (TransactionError::InstructionError(index, _), Some(tokenmill_error)) => {
+            format!("Error processing Instruction {}: {}", index, tokenmill_error)
+        }
+        _ => err.to_string(),
+    }
+}
+
+pub fn describe_client_error(err: &ClientError) -> String {
+    match (err.get_transaction_error(), preflight_logs(err).and_then(ProgramError::from_logs)) {
+        (Some(TransactionError::InstructionError(index, _)), Some(program_error))
+            if TokenMillError::from_code(program_error.code).is_none() =>
+        {
+            format!("Error processing Instruction {}: {}", index, program_error)
+        }
+        (Some(err), _) => describe_transaction_error(&err),
+        (None, _) => err.to_string(),
+    }
+}
+
+// For sending from the CLI, keeps the client error as the cause of the named program error
+pub fn decode_client_error(err: ClientError) -> anyhow::Error {
+    let tokenmill_error = err.get_transaction_error().as_ref().and_then(TokenMillError::from_transaction_error);
+    if let Some(tokenmill_error) = tokenmill_error {
+        return anyhow::Error::new(err).context(tokenmill_error);
+    }
+
+    match preflight_logs(&err).and_then(ProgramError::from_logs) {
+        Some(program_error) => anyhow::Error::new(err).context(program_error),
+        None => err.into(),
+    }
+}
@@ -0,0 +1,232 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    transaction::Transaction,
+};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+
+use crate::market::{Market, MARKET_DISCRIMINATOR, TOKENMILL_PROGRAM};
+use crate::supply::quote_reserve;
+use crate::swap::{find_event_authority, SwapOptions};
+use crate::token::{get_associated_token_address, token_account_amount};
+
+pub const CLAIM_CREATOR_FEES_DISCRIMINATOR: [u8; 8] = [0, 23, 125, 234, 156, 118, 134, 89];
+// Offset of `Market::creator`, right after the discriminator and the config
+const MARKET_CREATOR_OFFSET: usize = 8 + 32;
+// Maximum number of accounts of a getMultipleAccounts call
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+// Fees of a market claimable by its creator
+#[derive(Clone, Debug)]
+pub struct CreatorFees {
+    pub market_address: Pubkey,
+    pub market: Market,
+    pub token_program1: Pubkey,
+    pub claimable: u64,
+    // Only for markets keeping the creator fees apart from reserve 1
+    pub fee_reserve: Option<FeeReserve>,
+}
+
+// Token account of `Market::fee_reserve`, as fetched
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeeReserve {
+    pub address: Pubkey,
+    pub balance: u64,
+    // `Market::fee_reserve_last_update`, when the fees were last moved to it
+    pub last_update: i64,
+}
+
+// Markets with a fee reserve hold the creator share of the fees in it, so it's the balance of that account
+// Otherwise the protocol share leaves the market on every swap, while the creator share stays in reserve 1
+// until claimed, so it's whatever reserve 1 holds above what the curve owes to sellers
+pub fn claimable_creator_fees(market: &Market, reserve1_balance: u64, fee_reserve_balance: Option<u64>) -> Result<u64> {
+    match (market.fee_reserve, fee_reserve_balance) {
+        (Some(_), Some(balance)) => Ok(balance),
+        (Some(fee_reserve), None) => Err(anyhow!("FeeReserveMissing: balance of {} not given", fee_reserve)),
+        (None, _) => {
+            let curve_reserve = quote_reserve(&market.settings, market.sqrt_price_x96)?;
+
+            Ok(reserve1_balance.saturating_sub(curve_reserve))
+        }
+    }
+}
+
+pub fn fetch_creator_markets(rpc_client: &RpcClient, creator: &Pubkey) -> Result<Vec<(Pubkey, Market)>> {
+    let accounts = rpc_client
+        .get_program_accounts_with_config(
+            &Pubkey::from_str_const(TOKENMILL_PROGRAM),
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &MARKET_DISCRIMINATOR)),
+                    RpcFilterType::Memcmp(Memcmp::new_base58_encoded(MARKET_CREATOR_OFFSET, creator.as_ref())),
+                ]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .with_context(|| format!("Failed to fetch the markets of {}", creator))?;
+
+    let mut markets = accounts
+        .into_iter()
+        .map(|(address, account)| Ok((address, Market::from_bytes(&account.data)?)))
+        .collect::<Result<Vec<_>>>()?;
+    markets.sort_by_key(|(address, _)| *address);
+
+    Ok(markets)
+}
+
+pub fn fetch_creator_fees(rpc_client: &RpcClient, markets: &[(Pubkey, Market)]) -> Result<Vec<CreatorFees>> {
+    // Reserve 1 and token mint 1 of each market, then the fee reserves of the markets having one
+    let mut addresses = markets
+        .iter()
+        .flat_map(|(_, market)| [market.reserve1, market.token_mint1])
+        .collect::<Vec<_>>();
+    addresses.extend(markets.iter().filter_map(|(_, market)| market.fee_reserve));
+    let mut accounts = vec![];
+    for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        accounts.extend(rpc_client.get_multiple_accounts(chunk)?);
+    }
+    let mut fee_reserve_accounts = accounts.split_off(2 * markets.len()).into_iter();
+
+    markets
+        .iter()
+        .zip(accounts.chunks(2))
+        .map(|((market_address, market), accounts)| {
+            let reserve1 = accounts[0]
+                .as_ref()
+                .ok_or(anyhow!("Reserve 1 of market {} not found", market_address))?;
+            let token_mint1 = accounts[1]
+                .as_ref()
+                .ok_or(anyhow!("Token mint 1 of market {} not found", market_address))?;
+            let reserve1_balance = token_account_amount(&reserve1.data)
+                .ok_or(anyhow!("Invalid reserve 1 of market {}", market_address))?;
+
+            let fee_reserve = match market.fee_reserve {
+                Some(address) => {
+                    let account = fee_reserve_accounts
+                        .next()
+                        .flatten()
+                        .ok_or(anyhow!("Fee reserve {} of market {} not found", address, market_address))?;
+                    let balance = token_account_amount(&account.data)
+                        .ok_or(anyhow!("Invalid fee reserve {} of market {}", address, market_address))?;
+
+                    Some(FeeReserve { address, balance, last_update: market.fee_reserve_last_update })
+                }
+                None => None,
+            };
+
+            Ok(CreatorFees {
+                market_address: *market_address,
+                market: market.clone(),
+                token_program1: token_mint1.owner,
+                claimable: claimable_creator_fees(
+                    market,
+                    reserve1_balance,
+                    fee_reserve.as_ref().map(|fee_reserve| fee_reserve.balance),
+                )?,
+                fee_reserve,
+            })
+        })
+        .collect()
+}
+
+// Fees are paid in token 1 to the creator's associated token account
+pub fn build_claim_creator_fees_instruction(fees: &CreatorFees, creator: &Pubkey) -> Instruction {
+    let program_id = Pubkey::from_str_const(TOKENMILL_PROGRAM);
+    let market = &fees.market;
+    let creator_token_account1 = get_associated_token_address(creator, &market.token_mint1, &fees.token_program1);
+    // The fees of markets with a fee reserve are paid from it, the program id stands in for it on the others
+    let fee_reserve = match market.fee_reserve {
+        Some(fee_reserve) => AccountMeta::new(fee_reserve, false),
+        None => AccountMeta::new_readonly(program_id, false),
+    };
+
+    let instruction_accounts = vec![
+        AccountMeta::new_readonly(market.config, false), //#0 config
+        AccountMeta::new(fees.market_address, false), //#1 market
+        AccountMeta::new_readonly(market.token_mint1, false), //#2 token mint 1
+        AccountMeta::new(market.reserve1, false), //#3 market reserve 1
+        fee_reserve, //#4 market fee reserve
+        AccountMeta::new(creator_token_account1, false), //#5 creator token account 1
+        AccountMeta::new_readonly(*creator, true), //#6 creator
+        AccountMeta::new_readonly(fees.token_program1, false), //#7 token program 1
+        AccountMeta::new_readonly(find_event_authority(), false), //#8 event authority
+        AccountMeta::new_readonly(program_id, false), //#9 program
+    ];
+
+    Instruction {
+        program_id,
+        accounts: instruction_accounts,
+        data: CLAIM_CREATOR_FEES_DISCRIMINATOR.to_vec(),
+    }
+}
+
+// Packs the claims into as few transactions as fit in a packet, each one creating the token accounts it pays to
+pub fn build_claim_transactions(fees: &[CreatorFees], creator: &Pubkey, options: &SwapOptions) -> Result<Vec<Transaction>> {
+    let mut prefix = vec![];
+    if let Some(compute_unit_limit) = options.compute_unit_limit {
+        prefix.push(ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit));
+    }
+    if let Some(priority_fee) = options.priority_fee_micro_lamports {
+        prefix.push(ComputeBudgetInstruction::set_compute_unit_price(priority_fee));
+    }
+
+    let fits = |instructions: &[Instruction]| -> Result<bool> {
+        let transaction = Transaction::new_unsigned(Message::new(instructions, Some(creator)));
+        Ok(bincode::serialized_size(&transaction)? <= PACKET_DATA_SIZE as u64)
+    };
+
+    // Token accounts are created once per transaction
+    let claim = |fees: &CreatorFees, token_mints: &HashSet<Pubkey>| {
+        let mut instructions = vec![];
+        if !token_mints.contains(&fees.market.token_mint1) {
+            instructions.push(create_associated_token_account_idempotent(
+                creator,
+                creator,
+                &fees.market.token_mint1,
+                &fees.token_program1,
+            ));
+        }
+        instructions.push(build_claim_creator_fees_instruction(fees, creator));
+        instructions
+    };
+
+    let mut transactions = vec![];
+    let (mut instructions, mut token_mints) = (prefix.clone(), HashSet::new());
+    for fees in fees {
+        let mut candidate = [instructions.clone(), claim(fees, &token_mints)].concat();
+        if !fits(&candidate)? {
+            if instructions.len() > prefix.len() {
+                transactions.push(Transaction::new_unsigned(Message::new(&instructions, Some(creator))));
+            }
+            token_mints.clear();
+            candidate = [prefix.clone(), claim(fees, &token_mints)].concat();
+            if !fits(&candidate)? {
+                return Err(anyhow!("Claim of market {} doesn't fit in a transaction", fees.market_address));
+            }
+        }
+
+        instructions = candidate;
+        token_mints.insert(fees.market.token_mint1);
+    }
+    if instructions.len() > prefix.len() {
+        transactions.push(Transaction::new_unsigned(Message::new(&instructions, Some(creator))));
+    }
+
+    Ok(transactions)
+}
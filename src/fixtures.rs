@@ -0,0 +1,261 @@
+use anyhow::{anyhow, Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::{json, Value};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+
+use crate::market::Market;
+use crate::swap::SwapParameters;
+use crate::transaction::{decode_swaps, fetch_transaction};
+
+pub const FIXTURES_DIR: &str = "fixtures/swaps";
+
+// A swap observed on chain, replayed by the conformance tests against the local quote
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapFixture {
+    pub signature: String,
+    pub slot: u64,
+    pub market_address: Pubkey,
+    // Market account data right before the swap
+    pub market_data: Vec<u8>,
+    pub parameters: SwapParameters,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    // Market price right after the swap, only known when it was recorded before the next swap on the market
+    pub sqrt_price_x96: Option<u128>,
+}
+
+impl SwapFixture {
+    pub fn market(&self) -> Result<Market> {
+        Market::from_bytes(&self.market_data)
+            .with_context(|| format!("Failed to decode market of fixture {}", self.signature))
+    }
+
+    // u128 values are serialized as strings, as they don't fit in a JSON number
+    pub fn to_json(&self) -> Value {
+        json!({
+            "signature": self.signature,
+            "slot": self.slot,
+            "market": self.market_address.to_string(),
+            "market_data": general_purpose::STANDARD.encode(&self.market_data),
+            "parameters": {
+                "side": self.parameters.side(),
+                "mode": self.parameters.mode(),
+                "amount": self.parameters.amount(),
+                "threshold": self.parameters.threshold(),
+            },
+            "amount_in": self.amount_in,
+            "amount_out": self.amount_out,
+            "sqrt_price_x96": self.sqrt_price_x96.map(|sqrt_price| sqrt_price.to_string()),
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let str_field = |value: &Value, key: &str| -> Result<String> {
+            value
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or(anyhow!("Missing {}", key))
+        };
+        let u64_field = |value: &Value, key: &str| -> Result<u64> {
+            value.get(key).and_then(Value::as_u64).ok_or(anyhow!("Missing {}", key))
+        };
+
+        let parameters = value.get("parameters").ok_or(anyhow!("Missing parameters"))?;
+        let market_address = str_field(value, "market")?;
+        let sqrt_price_x96 = match value.get("sqrt_price_x96") {
+            None | Some(Value::Null) => None,
+            Some(sqrt_price) => Some(
+                sqrt_price
+                    .as_str()
+                    .and_then(|sqrt_price| sqrt_price.parse::<u128>().ok())
+                    .ok_or(anyhow!("Invalid sqrt_price_x96: {}", sqrt_price))?,
+            ),
+        };
+
+        Ok(Self {
+            signature: str_field(value, "signature")?,
+            slot: u64_field(value, "slot")?,
+            market_address: Pubkey::from_str(&market_address)
+                .map_err(|_| anyhow!("Invalid address: {}", market_address))?,
+            market_data: general_purpose::STANDARD
+                .decode(str_field(value, "market_data")?)
+                .context("Invalid market_data")?,
+            parameters: SwapParameters::parse(
+                &str_field(parameters, "side")?,
+                &str_field(parameters, "mode")?,
+                u64_field(parameters, "amount")?,
+                u64_field(parameters, "threshold")?,
+            )?,
+            amount_in: u64_field(value, "amount_in")?,
+            amount_out: u64_field(value, "amount_out")?,
+            sqrt_price_x96,
+        })
+    }
+
+    // One file per transaction, named after its signature
+    pub fn write(&self, dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(format!("{}.json", self.signature));
+        fs::write(&path, serde_json::to_string_pretty(&self.to_json())? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        Ok(path)
+    }
+}
+
+// Every `.json` file of the directory, sorted by file name
+pub fn load_fixtures(dir: &Path) -> Result<Vec<SwapFixture>> {
+    let mut paths = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| path.extension().is_some_and(|extension| extension == "json"));
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            let value = serde_json::from_str(&content).with_context(|| format!("Invalid JSON in {}", path.display()))?;
+            SwapFixture::from_json(&value).with_context(|| format!("Invalid fixture {}", path.display()))
+        })
+        .collect()
+}
+
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_millis(400);
+
+// Market account data right after a transaction, fetched while it was still the last one on the market
+struct MarketSnapshot {
+    signature: String,
+    data: Vec<u8>,
+}
+
+// The RPC only serves current account states, so the state a swap starts from is the one fetched while the
+// transaction before it was still the last one on the market, which takes watching the market before the swap lands
+// Records the next `count` swaps landing on the market, passing each one to `on_fixture` as it's recorded
+// A swap is only recorded when it's the first transaction after the last state fetched and the only swap of its
+// transaction on the market, its price after the swap when no other transaction followed it before the next fetch
+pub async fn watch_fixtures(
+    rpc_client: &RpcClient,
+    market_address: &Pubkey,
+    count: usize,
+    poll_interval: Duration,
+    mut on_fixture: impl FnMut(&SwapFixture) -> Result<()>,
+) -> Result<()> {
+    let mut snapshot = fetch_snapshot(rpc_client, market_address).await?;
+    let mut recorded = 0;
+
+    while recorded < count {
+        tokio::time::sleep(poll_interval).await;
+
+        // Newest first
+        let signatures = rpc_client
+            .get_signatures_for_address_with_config(
+                market_address,
+                GetConfirmedSignaturesForAddress2Config {
+                    until: Some(Signature::from_str(&snapshot.signature)?),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .with_context(|| format!("Failed to fetch the transactions of market {}", market_address))?;
+        let Some(next) = signatures.last() else {
+            continue;
+        };
+
+        let after = fetch_snapshot(rpc_client, market_address).await?;
+        if next.err.is_none() {
+            let sqrt_price_x96 = match after.signature == next.signature {
+                true => Some(Market::from_bytes(&after.data)?.sqrt_price_x96),
+                false => None,
+            };
+            let signature = Signature::from_str(&next.signature)?;
+            if let Some(fixture) =
+                record_fixture(rpc_client, &signature, market_address, &snapshot.data, sqrt_price_x96).await?
+            {
+                on_fixture(&fixture)?;
+                recorded += 1;
+            }
+        }
+        snapshot = after;
+    }
+
+    Ok(())
+}
+
+// The swap of the transaction on the market, started from `market_data`
+// None when the transaction has no swap on the market, or several of them, as only the first one starts from it
+async fn record_fixture(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    market_address: &Pubkey,
+    market_data: &[u8],
+    sqrt_price_x96: Option<u128>,
+) -> Result<Option<SwapFixture>> {
+    let transaction = fetch_transaction(rpc_client, signature).await?;
+    let swaps = decode_swaps(&transaction)?
+        .into_iter()
+        .filter(|swap| swap.market == *market_address)
+        .collect::<Vec<_>>();
+    let [swap] = swaps.as_slice() else {
+        return Ok(None);
+    };
+    let (Some(amount_in), Some(amount_out)) = (swap.amount_in, swap.amount_out) else {
+        return Ok(None);
+    };
+
+    Ok(Some(SwapFixture {
+        signature: signature.to_string(),
+        slot: transaction.slot,
+        market_address: *market_address,
+        market_data: market_data.to_vec(),
+        parameters: swap.parameters.clone(),
+        amount_in,
+        amount_out,
+        sqrt_price_x96,
+    }))
+}
+
+// The last transaction on the market is read again after the fetch, in case another one landed in between
+async fn fetch_snapshot(rpc_client: &RpcClient, market_address: &Pubkey) -> Result<MarketSnapshot> {
+    let last_signature = || async {
+        let signatures = rpc_client
+            .get_signatures_for_address_with_config(
+                market_address,
+                GetConfirmedSignaturesForAddress2Config {
+                    limit: Some(1),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        signatures
+            .into_iter()
+            .next()
+            .map(|last| last.signature)
+            .ok_or(anyhow!("Market {} has no transaction", market_address))
+    };
+
+    loop {
+        let signature = last_signature().await?;
+        let account = rpc_client
+            .get_account_with_commitment(market_address, CommitmentConfig::confirmed())
+            .await?
+            .value
+            .ok_or(anyhow!("Market {} not found", market_address))?;
+        if last_signature().await? == signature {
+            return Ok(MarketSnapshot { signature, data: account.data });
+        }
+    }
+}
@@ -0,0 +1,31 @@
+pub mod error;
+pub mod math;
+pub mod swap_math;
+pub mod quote;
+pub mod market;
+pub mod price;
+pub mod swap;
+pub mod store;
+pub mod server;
+pub mod token;
+pub mod transaction;
+pub mod fixtures;
+pub mod sizing;
+pub mod target;
+pub mod depth;
+pub mod supply;
+pub mod design;
+pub mod create_market;
+pub mod fees;
+pub mod authority;
+pub mod state;
+pub mod backtest;
+pub mod route;
+pub mod bundle;
+pub mod tracker;
+pub mod retry;
+pub mod portfolio;
+pub mod sweep;
+
+#[cfg(test)]
+mod tests;
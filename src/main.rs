@@ -47,26 +47,915 @@ use borsh::BorshSerialize;
 
 use std::convert::TryInto;
 
-mod math;
-mod swap_math;
-mod quote;
-mod market;
-
-#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub enum SwapParameters {
-    BuyExactIn(u64, u64),
-    BuyExactOut(u64, u64),
-    SellExactIn(u64, u64),
-    SellExactOut(u64, u64),
+use noierrdev_tokenmill_swap_sample::{authority, backtest, bundle, create_market, depth, design, error, fees, fixtures, market, portfolio, price, quote, retry, route, server, swap, sweep};
+
+#[derive(Parser)]
+#[command(about = "TokenMill swap sample")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Run the HTTP quote service
+    Serve {
+        #[arg(long, default_value = "0.0.0.0:8080")]
+        bind: String,
+        /// How long a fetched market state is reused before being refetched
+        #[arg(long, default_value_t = 1000)]
+        cache_ttl_ms: u64,
+        /// Traders the swap authority co-signs swaps for
+        #[arg(long = "allowed-user")]
+        allowed_users: Vec<String>,
+        /// Markets the swap authority co-signs swaps on
+        #[arg(long = "allowed-market")]
+        allowed_markets: Vec<String>,
+    },
+    /// Build, sign and send a swap with the wallet from PRIVATE_KEY
+    Swap {
+        market: String,
+        /// buy or sell
+        #[arg(long)]
+        side: String,
+        /// exact_in or exact_out
+        #[arg(long, default_value = "exact_in")]
+        mode: String,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long, default_value_t = 100)]
+        slippage_bps: u64,
+        #[arg(long)]
+        priority_fee_micro_lamports: Option<u64>,
+        /// Service adding the swap authority signature on restricted markets,
+        /// unless SWAP_AUTHORITY_PRIVATE_KEY holds the authority locally
+        #[arg(long)]
+        cosigner_url: Option<String>,
+        /// Sends again, re-quoted, after slippage, expired blockhash or account in use failures
+        #[arg(long, default_value_t = retry::DEFAULT_MAX_ATTEMPTS)]
+        max_attempts: usize,
+        /// Worst average price accepted by any attempt, in raw token 1 units per token 0 unit:
+        /// the highest for buys, the lowest for sells
+        #[arg(long)]
+        price_limit: Option<f64>,
+    },
+    /// Swap a token for another through the markets sharing their quote token, in a single transaction
+    Route {
+        input_mint: String,
+        output_mint: String,
+        #[arg(long)]
+        amount: u64,
+        /// Taken on every hop, the last threshold protecting the whole route
+        #[arg(long, default_value_t = 100)]
+        slippage_bps: u64,
+        #[arg(long, default_value_t = route::DEFAULT_MAX_HOPS)]
+        max_hops: usize,
+        /// Markets to route through on top of the ones of both mints
+        #[arg(long, value_delimiter = ',')]
+        markets: Vec<String>,
+        /// Print the route without sending it
+        #[arg(long)]
+        dry_run: bool,
+        #[arg(long)]
+        priority_fee_micro_lamports: Option<u64>,
+    },
+    /// Buy then sell on a market in a single Jito bundle, if the round trip nets enough after its costs
+    Bundle {
+        market: String,
+        /// Lamports spent on the buy
+        #[arg(long)]
+        amount: u64,
+        #[arg(long, default_value_t = 100)]
+        slippage_bps: u64,
+        #[arg(long, default_value_t = 10_000)]
+        tip_lamports: u64,
+        #[arg(long, default_value_t = 200_000)]
+        compute_unit_limit: u32,
+        #[arg(long, default_value_t = 0)]
+        priority_fee_micro_lamports: u64,
+        /// Worst case net of the round trip in lamports, negative to accept a loss
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        min_net_lamports: i64,
+        #[arg(long, default_value = bundle::DEFAULT_BLOCK_ENGINE_URL)]
+        block_engine_url: String,
+        /// Print the round trip without sending it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List the token accounts of a wallet, valuing the TokenMill tokens on their curve
+    Portfolio {
+        /// Defaults to the wallet from PRIVATE_KEY
+        owner: Option<String>,
+    },
+    /// Sell the TokenMill positions of the wallet worth little, then close its empty and wSOL token accounts
+    Sweep {
+        /// Positions returning at most this much when sold are sold, in raw units of their quote token
+        #[arg(long)]
+        max_value: u64,
+        #[arg(long, default_value_t = 100)]
+        slippage_bps: u64,
+        /// Print what would be sold and closed without sending anything
+        #[arg(long)]
+        dry_run: bool,
+        #[arg(long)]
+        priority_fee_micro_lamports: Option<u64>,
+    },
+    /// Print the depth of a market in both directions, as a synthetic order book
+    Depth {
+        market: String,
+        /// Price moves from the current price, in bps
+        #[arg(long, value_delimiter = ',', default_value = "100,200,500,1000,2000,5000")]
+        price_steps_bps: Vec<u64>,
+        /// Cumulative buy sizes in token 1, used instead of the price steps for buys
+        #[arg(long, value_delimiter = ',')]
+        buy_sizes: Vec<u64>,
+        /// Cumulative sell sizes in token 0, used instead of the price steps for sells
+        #[arg(long, value_delimiter = ',')]
+        sell_sizes: Vec<u64>,
+        /// json or csv
+        #[arg(long, default_value = "json")]
+        format: String,
+    },
+    /// Derive the market settings of a curve from its prices and supplies
+    Design {
+        #[command(flatten)]
+        curve: CurveArgs,
+        /// Number of intervals of the printed price curve
+        #[arg(long, default_value_t = 20)]
+        points: usize,
+    },
+    /// Create a market on a designed curve with the wallet from PRIVATE_KEY as creator
+    CreateMarket {
+        #[command(flatten)]
+        curve: CurveArgs,
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        symbol: String,
+        #[arg(long)]
+        uri: String,
+        /// TokenMill config the market is created under
+        #[arg(long)]
+        config: String,
+        #[arg(long, default_value = "So11111111111111111111111111111111111111112")]
+        token_mint1: String,
+        /// Create token 0 as an SPL mint with Metaplex metadata instead of a Token 2022 mint
+        #[arg(long)]
+        spl: bool,
+        /// Key that has to co-sign every swap on the market
+        #[arg(long)]
+        swap_authority: Option<String>,
+        #[arg(long)]
+        priority_fee_micro_lamports: Option<u64>,
+    },
+    /// See and claim the creator fees of the markets created by the wallet from PRIVATE_KEY
+    Fees {
+        #[command(subcommand)]
+        command: FeesCommand,
+    },
+    /// Replay historical swaps with a strategy sniping each market and exiting on take profit or stop loss
+    Backtest {
+        /// Swaps as .csv or .json, the first one of each market carrying its market data
+        #[arg(long, conflicts_with = "fixtures", required_unless_present = "fixtures")]
+        events: Option<String>,
+        /// Replay the recorded swap vectors instead
+        #[arg(long)]
+        fixtures: Option<String>,
+        /// Token 1 spent on the first swap seen on each market
+        #[arg(long)]
+        buy_amount: u64,
+        #[arg(long, default_value_t = 5_000)]
+        take_profit_bps: u64,
+        #[arg(long, default_value_t = 2_000)]
+        stop_loss_bps: u64,
+    },
+    /// Manage the golden swap vectors replayed by the conformance tests
+    Fixtures {
+        #[command(subcommand)]
+        command: FixturesCommand,
+    },
+}
+
+#[derive(clap::Args)]
+struct CurveArgs {
+    /// Prices in whole token 1 per whole token 0
+    #[arg(long)]
+    start_price: f64,
+    #[arg(long)]
+    boundary_price: f64,
+    #[arg(long)]
+    end_price: f64,
+    /// Supplies sold in each phase, in raw token 0 units
+    #[arg(long)]
+    supply_a: u64,
+    #[arg(long)]
+    supply_b: u64,
+    #[arg(long, default_value_t = 6)]
+    decimals0: u8,
+    #[arg(long, default_value_t = 9)]
+    decimals1: u8,
+    /// Swap fee, in millionths
+    #[arg(long, default_value_t = 0)]
+    fee: u32,
+}
+
+impl CurveArgs {
+    fn design(&self) -> design::CurveDesign {
+        design::CurveDesign {
+            start_price: self.start_price,
+            boundary_price: self.boundary_price,
+            end_price: self.end_price,
+            supply_a: self.supply_a,
+            supply_b: self.supply_b,
+            decimals0: self.decimals0,
+            decimals1: self.decimals1,
+            fee: self.fee,
+        }
+    }
+}
+
+#[derive(clap::Subcommand)]
+enum FeesCommand {
+    /// List the claimable fees of every market created by the wallet
+    List,
+    /// Claim the fees of the given markets, batched into as few transactions as possible
+    Claim {
+        markets: Vec<String>,
+        /// Claim every market created by the wallet
+        #[arg(long)]
+        all: bool,
+        /// Skip markets with less claimable fees, in raw token 1 units
+        #[arg(long, default_value_t = 1)]
+        min_amount: u64,
+        #[arg(long)]
+        priority_fee_micro_lamports: Option<u64>,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum FixturesCommand {
+    /// Watch a market and record the next swaps landing on it as new vectors
+    Record {
+        market: String,
+        /// Number of swaps to record
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+        #[arg(long, default_value = fixtures::FIXTURES_DIR)]
+        dir: String,
+    },
+    /// Replay every recorded vector through the local quote and list the divergences
+    Check {
+        #[arg(long, default_value = fixtures::FIXTURES_DIR)]
+        dir: String,
+    },
 }
 
 
 #[tokio::main]
-async fn main() {
-    
+async fn main() -> Result<()> {
+
     dotenv::dotenv().ok();
 
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Serve {
+            bind,
+            cache_ttl_ms,
+            allowed_users,
+            allowed_markets,
+        }) => {
+            let rpc_url = env::var("RPC_API").context("RPC_API is not set")?;
+            // Optional key sponsoring the fees of the transactions built by the service
+            let fee_payer = match env::var("FEE_PAYER_PRIVATE_KEY") {
+                Ok(private_key_str) => Some(keypair_from_base58(&private_key_str)?),
+                Err(_) => None,
+            };
+            // Optional key co-signing the swaps on the markets it restricts
+            let swap_authority = load_swap_authority()?;
+            let swap_allowlist = authority::SwapAllowlist {
+                users: allowed_users.iter().map(|user| server::parse_pubkey(user)).collect::<Result<_>>()?,
+                markets: allowed_markets.iter().map(|market| server::parse_pubkey(market)).collect::<Result<_>>()?,
+            };
+            server::serve(
+                &bind,
+                rpc_url,
+                Duration::from_millis(cache_ttl_ms),
+                fee_payer,
+                swap_authority,
+                swap_allowlist,
+            )
+            .await
+        }
+        Some(Command::Swap {
+            market,
+            side,
+            mode,
+            amount,
+            slippage_bps,
+            priority_fee_micro_lamports,
+            cosigner_url,
+            max_attempts,
+            price_limit,
+        }) => {
+            let parameters = swap::SwapParameters::parse(&side, &mode, amount, 0)?;
+            let policy = retry::RetryPolicy { max_attempts, slippage_bps, price_limit };
+            run_swap(&market, parameters, &policy, priority_fee_micro_lamports, cosigner_url.as_deref()).await
+        }
+        Some(Command::Route {
+            input_mint,
+            output_mint,
+            amount,
+            slippage_bps,
+            max_hops,
+            markets,
+            dry_run,
+            priority_fee_micro_lamports,
+        }) => {
+            let route = find_route(&input_mint, &output_mint, amount, slippage_bps, max_hops, &markets)?;
+            if dry_run {
+                return Ok(());
+            }
+            send_route(&route, priority_fee_micro_lamports)
+        }
+        Some(Command::Bundle {
+            market,
+            amount,
+            slippage_bps,
+            tip_lamports,
+            compute_unit_limit,
+            priority_fee_micro_lamports,
+            min_net_lamports,
+            block_engine_url,
+            dry_run,
+        }) => {
+            let costs = bundle::BundleCosts {
+                tip_lamports,
+                compute_unit_limit,
+                priority_fee_micro_lamports,
+            };
+            let block_engine_url = (!dry_run).then_some(block_engine_url.as_str());
+            run_bundle(&market, amount, slippage_bps, &costs, min_net_lamports, block_engine_url).await
+        }
+        Some(Command::Portfolio { owner }) => print_portfolio(owner.as_deref()),
+        Some(Command::Sweep { max_value, slippage_bps, dry_run, priority_fee_micro_lamports }) => {
+            run_sweep(max_value, slippage_bps, dry_run, priority_fee_micro_lamports)
+        }
+        Some(Command::Depth { market, price_steps_bps, buy_sizes, sell_sizes, format }) => {
+            print_depth(&market, &price_steps_bps, &buy_sizes, &sell_sizes, &format)
+        }
+        Some(Command::Design { curve, points }) => print_curve_design(&curve.design(), points),
+        Some(Command::CreateMarket {
+            curve,
+            name,
+            symbol,
+            uri,
+            config,
+            token_mint1,
+            spl,
+            swap_authority,
+            priority_fee_micro_lamports,
+        }) => {
+            let swap_authority = swap_authority.as_deref().map(server::parse_pubkey).transpose()?;
+            let args = create_market::CreateMarketArgs {
+                name,
+                symbol,
+                uri,
+                settings: design::design_curve(&curve.design(), 0)?.settings,
+                swap_authority,
+            };
+            let standard = if spl { create_market::MintStandard::Spl } else { create_market::MintStandard::Token2022 };
+            run_create_market(&config, &token_mint1, standard, &args, priority_fee_micro_lamports)
+        }
+        Some(Command::Fees { command: FeesCommand::List }) => list_creator_fees(),
+        Some(Command::Fees { command: FeesCommand::Claim { markets, all, min_amount, priority_fee_micro_lamports } }) => {
+            claim_creator_fees(&markets, all, min_amount, priority_fee_micro_lamports)
+        }
+        Some(Command::Backtest { events, fixtures, buy_amount, take_profit_bps, stop_loss_bps }) => {
+            let mut strategy = backtest::SnipeAndExit::new(buy_amount, take_profit_bps, stop_loss_bps);
+            print_backtest(events.as_deref(), fixtures.as_deref(), &mut strategy)
+        }
+        Some(Command::Fixtures { command: FixturesCommand::Record { market, count, dir } }) => {
+            record_fixtures(&market, count, &dir).await
+        }
+        Some(Command::Fixtures { command: FixturesCommand::Check { dir } }) => check_fixtures(&dir),
+        None => {
+            run_sample().await;
+            Ok(())
+        }
+    }
+}
+
+fn keypair_from_base58(private_key_str: &str) -> Result<Keypair> {
+    let private_key_bytes = bs58::decode(private_key_str)
+        .into_vec()
+        .ok()
+        .context("Invalid base58 private key")?;
+    Keypair::from_bytes(&private_key_bytes).context("Invalid private key")
+}
+
+fn load_wallet() -> Result<Keypair> {
+    keypair_from_base58(&env::var("PRIVATE_KEY").context("PRIVATE_KEY is not set")?)
+}
+
+fn load_swap_authority() -> Result<Option<Keypair>> {
+    match env::var("SWAP_AUTHORITY_PRIVATE_KEY") {
+        Ok(private_key_str) => Ok(Some(keypair_from_base58(&private_key_str)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn load_rpc_client() -> Result<RpcClient> {
+    let rpc_url = env::var("RPC_API").context("RPC_API is not set")?;
+    Ok(RpcClient::new_with_commitment(rpc_url, CommitmentConfig::processed()))
+}
+
+// For the commands waiting on the chain from async code
+fn load_nonblocking_rpc_client() -> Result<solana_client::nonblocking::rpc_client::RpcClient> {
+    let rpc_url = env::var("RPC_API").context("RPC_API is not set")?;
+    Ok(solana_client::nonblocking::rpc_client::RpcClient::new_with_commitment(rpc_url, CommitmentConfig::processed()))
+}
+
+// Resolves the token programs owning both mints of a market
+fn fetch_token_programs(rpc_client: &RpcClient, market: &market::Market) -> Result<(Pubkey, Pubkey)> {
+    let mints = rpc_client.get_multiple_accounts(&[market.token_mint0, market.token_mint1])?;
+    let token_program0 = mints[0].as_ref().context("Token mint 0 not found")?.owner;
+    let token_program1 = mints[1].as_ref().context("Token mint 1 not found")?.owner;
+
+    Ok((token_program0, token_program1))
+}
+
+async fn run_swap(
+    market_address: &str,
+    parameters: swap::SwapParameters,
+    policy: &retry::RetryPolicy,
+    priority_fee_micro_lamports: Option<u64>,
+    cosigner_url: Option<&str>,
+) -> Result<()> {
+    let wallet = load_wallet()?;
+    let rpc_client = load_rpc_client()?;
+
+    let market_address = server::parse_pubkey(market_address)?;
+    let market = market::Market::from_bytes(&rpc_client.get_account(&market_address)?.data)?;
+
+    // Restricted markets need the authority signature, from the wallet itself, a local key or the co-signer
+    let swap_authority = load_swap_authority()?
+        .filter(|keypair| market.swap_authority == Some(keypair.pubkey()) && keypair.pubkey() != wallet.pubkey());
+    let mut signers = vec![wallet.pubkey()];
+    signers.extend(swap_authority.as_ref().map(|keypair| keypair.pubkey()));
+    let cosigner_url = match (authority::check_swap_access(&market_address, &market, &signers), cosigner_url) {
+        (Ok(()), _) => None,
+        (Err(_), Some(cosigner_url)) => Some(cosigner_url),
+        (Err(err), None) => return Err(err),
+    };
+
+    let (token_program0, token_program1) = fetch_token_programs(&rpc_client, &market)?;
+    let execution = retry::SwapExecution {
+        market_address,
+        accounts: swap::SwapAccounts::new(&market_address, &market, &token_program0, &token_program1),
+        parameters,
+        options: swap::SwapOptions {
+            compute_unit_limit: None,
+            priority_fee_micro_lamports,
+        },
+        wallet: &wallet,
+        swap_authority: swap_authority.as_ref(),
+        cosigner_url,
+    };
+
+    let report = retry::execute_swap(&load_nonblocking_rpc_client()?, &Client::new(), &execution, policy, |attempt| {
+        println!("{}", attempt.to_json());
+    })
+    .await?;
+    println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+
+    Ok(())
+}
+
+fn find_route(
+    input_mint: &str,
+    output_mint: &str,
+    amount: u64,
+    slippage_bps: u64,
+    max_hops: usize,
+    market_addresses: &[String],
+) -> Result<route::RouteQuote> {
+    let rpc_client = load_rpc_client()?;
+
+    let input_mint = server::parse_pubkey(input_mint)?;
+    let output_mint = server::parse_pubkey(output_mint)?;
+    // A launched token has a single market, derived from its mint
+    let mut addresses = vec![market::Market::find_pda(&input_mint).0, market::Market::find_pda(&output_mint).0];
+    for address in market_addresses {
+        let address = server::parse_pubkey(address)?;
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    let mut markets = vec![];
+    for (address, account) in addresses.iter().zip(rpc_client.get_multiple_accounts(&addresses)?) {
+        let Some(account) = account else {
+            continue;
+        };
+        let market = market::Market::from_bytes(&account.data)?;
+        let (token_program0, token_program1) = fetch_token_programs(&rpc_client, &market)?;
+        markets.push(route::RouteMarket {
+            accounts: swap::SwapAccounts::new(address, &market, &token_program0, &token_program1),
+            market,
+        });
+    }
+
+    let route = route::best_route(&markets, &input_mint, &output_mint, amount, slippage_bps, max_hops)?;
+    for (hop, swap) in route.hops.iter().zip(route.swaps.iter()) {
+        println!(
+            "{} {} : {} {} -> {} {} (min {})",
+            swap.parameters.side(),
+            hop.market.accounts.market,
+            swap.quote.amount_in,
+            hop.mint_in(),
+            swap.quote.amount_out,
+            hop.mint_out(),
+            swap.parameters.threshold()
+        );
+    }
+    println!("Amount out : {} (min {})", route.amount_out, route.min_amount_out);
+    for (mint, amount) in route.leftovers.iter() {
+        println!("Left over : {} {}", amount, mint);
+    }
+
+    Ok(route)
+}
+
+fn send_route(route: &route::RouteQuote, priority_fee_micro_lamports: Option<u64>) -> Result<()> {
+    let wallet = load_wallet()?;
+    let rpc_client = load_rpc_client()?;
+
+    let options = swap::SwapOptions {
+        compute_unit_limit: None,
+        priority_fee_micro_lamports,
+    };
+    let mut transaction = route::build_route_transaction(route, &wallet.pubkey(), &options);
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    transaction.sign(&[&wallet], recent_blockhash);
+
+    let signature = rpc_client.send_and_confirm_transaction(&transaction).map_err(error::decode_client_error)?;
+    println!("Signature : {}", signature);
+
+    Ok(())
+}
+
+// Only prints the round trip without a block engine to send it to
+async fn run_bundle(
+    market_address: &str,
+    amount: u64,
+    slippage_bps: u64,
+    costs: &bundle::BundleCosts,
+    min_net_lamports: i64,
+    block_engine_url: Option<&str>,
+) -> Result<()> {
+    let wallet = load_wallet()?;
+    let rpc_client = load_rpc_client()?;
+
+    let market_address = server::parse_pubkey(market_address)?;
+    let market = market::Market::from_bytes(&rpc_client.get_account(&market_address)?.data)?;
+    authority::check_swap_access(&market_address, &market, &[wallet.pubkey()])?;
+
+    let round_trip = bundle::plan_round_trip(&market, amount, slippage_bps, costs, min_net_lamports)?;
+    println!("Buy : {:?} {:?}", round_trip.buy.parameters, round_trip.buy.quote);
+    println!("Sell : {:?} {:?}", round_trip.sell.parameters, round_trip.sell.quote);
+    println!("Net : {} lamports expected, {} at worst", round_trip.expected_net, round_trip.worst_net);
+    let Some(block_engine_url) = block_engine_url else {
+        return Ok(());
+    };
+
+    let (token_program0, token_program1) = fetch_token_programs(&rpc_client, &market)?;
+    let accounts = swap::SwapAccounts::new(&market_address, &market, &token_program0, &token_program1);
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    // Spreads the tips over the tip accounts
+    let tip_account = bundle::JITO_TIP_ACCOUNTS[usize::from(recent_blockhash.as_ref()[0]) % bundle::JITO_TIP_ACCOUNTS.len()];
+    let transactions = bundle::build_round_trip_bundle(
+        &accounts,
+        &round_trip,
+        &wallet,
+        &Pubkey::from_str_const(tip_account),
+        recent_blockhash,
+    );
+
+    let bundle_id = bundle::send_bundle(&Client::new(), block_engine_url, &transactions).await?;
+    println!("Bundle ID : {}", bundle_id);
+    for transaction in transactions.iter() {
+        println!("Signature : {}", transaction.signatures[0]);
+    }
+
+    Ok(())
+}
+
+fn print_portfolio(owner: Option<&str>) -> Result<()> {
+    let owner = match owner {
+        Some(owner) => server::parse_pubkey(owner)?,
+        None => load_wallet()?.pubkey(),
+    };
+    let rpc_client = load_rpc_client()?;
+
+    let positions = portfolio::fetch_portfolio(&rpc_client, &owner)?;
+    let totals = portfolio::portfolio_totals(&positions)
+        .into_iter()
+        .map(|(token_mint1, valuation)| {
+            json!({
+                "token_mint1": token_mint1.to_string(),
+                "spot_value": valuation.spot_value,
+                "liquidation_value": valuation.liquidation_value,
+                "price_impact_bps": valuation.price_impact_bps,
+            })
+        })
+        .collect::<Vec<_>>();
+    let portfolio = json!({
+        "owner": owner.to_string(),
+        "positions": positions.iter().map(portfolio::PortfolioPosition::to_json).collect::<Vec<_>>(),
+        "totals": totals,
+    });
+    println!("{}", serde_json::to_string_pretty(&portfolio)?);
+
+    Ok(())
+}
+
+fn run_sweep(max_value: u64, slippage_bps: u64, dry_run: bool, priority_fee_micro_lamports: Option<u64>) -> Result<()> {
+    let wallet = load_wallet()?;
+    let rpc_client = load_rpc_client()?;
+
+    let positions = portfolio::fetch_portfolio(&rpc_client, &wallet.pubkey())?;
+    let mut token_mints1 = positions
+        .iter()
+        .filter_map(|position| position.market.as_ref().map(|(_, market)| market.token_mint1))
+        .collect::<Vec<_>>();
+    token_mints1.sort();
+    token_mints1.dedup();
+    let mut token_programs1 = std::collections::HashMap::new();
+    for chunk in token_mints1.chunks(fees::MAX_MULTIPLE_ACCOUNTS) {
+        for (mint, account) in chunk.iter().zip(rpc_client.get_multiple_accounts(chunk)?) {
+            let account = account.with_context(|| format!("Token mint {} not found", mint))?;
+            token_programs1.insert(*mint, account.owner);
+        }
+    }
+
+    let plan = sweep::plan_sweep(&positions, &wallet.pubkey(), &token_programs1, max_value, slippage_bps)?;
+    let options = swap::SwapOptions {
+        compute_unit_limit: None,
+        priority_fee_micro_lamports,
+    };
+    let transactions = sweep::build_sweep_transactions(&plan, &wallet.pubkey(), &options)?;
+    println!("{}", serde_json::to_string_pretty(&plan.to_json())?);
+    println!(
+        "Sweeping in {} transactions, one per sale then the closes, a failing sale leaving its account open",
+        transactions.len()
+    );
+    if dry_run {
+        return Ok(());
+    }
+
+    for mut transaction in transactions {
+        let recent_blockhash = rpc_client.get_latest_blockhash()?;
+        transaction.sign(&[&wallet], recent_blockhash);
+        let signature = rpc_client.send_and_confirm_transaction(&transaction).map_err(error::decode_client_error)?;
+        println!("Signature : {}", signature);
+    }
+
+    Ok(())
+}
+
+fn print_depth(market_address: &str, price_steps_bps: &[u64], buy_sizes: &[u64], sell_sizes: &[u64], format: &str) -> Result<()> {
+    let rpc_client = load_rpc_client()?;
+
+    let market_address = server::parse_pubkey(market_address)?;
+    let market = market::Market::from_bytes(&rpc_client.get_account(&market_address)?.data)?;
+
+    let side = |zero_for_one: bool, sizes: &[u64]| -> Result<Vec<depth::DepthLevel>> {
+        if sizes.is_empty() {
+            Ok(depth::depth_by_price(&market, zero_for_one, price_steps_bps))
+        } else {
+            depth::depth_by_size(&market, zero_for_one, sizes)
+        }
+    };
+    let buys = side(false, buy_sizes)?;
+    let sells = side(true, sell_sizes)?;
+
+    match format {
+        "json" => {
+            let levels = |levels: &[depth::DepthLevel]| levels.iter().map(depth::DepthLevel::to_json).collect::<Vec<_>>();
+            let depth = json!({
+                "market": market_address.to_string(),
+                "sqrt_price_x96": market.sqrt_price_x96.to_string(),
+                "buy": levels(&buys),
+                "sell": levels(&sells),
+            });
+            println!("{}", serde_json::to_string_pretty(&depth)?);
+        }
+        "csv" => print!("{}", depth::depth_to_csv(&[buys, sells].concat())),
+        _ => return Err(anyhow::anyhow!("Invalid format: {}", format)),
+    }
+
+    Ok(())
+}
+
+fn run_create_market(
+    config: &str,
+    token_mint1: &str,
+    standard: create_market::MintStandard,
+    args: &create_market::CreateMarketArgs,
+    priority_fee_micro_lamports: Option<u64>,
+) -> Result<()> {
+    let wallet = load_wallet()?;
+    let rpc_client = load_rpc_client()?;
+
+    let config = server::parse_pubkey(config)?;
+    let token_mint1 = server::parse_pubkey(token_mint1)?;
+    let token_program1 = rpc_client.get_account(&token_mint1).context("Token mint 1 not found")?.owner;
+    let mint_keypair = Keypair::new();
+
+    let accounts =
+        create_market::CreateMarketAccounts::new(&config, &mint_keypair.pubkey(), &token_mint1, standard, &token_program1);
+    let options = swap::SwapOptions {
+        compute_unit_limit: None,
+        priority_fee_micro_lamports,
+    };
+    println!("Market : {}", accounts.market);
+    println!("Token mint 0 : {}", accounts.token_mint0);
+
+    let mut transaction = create_market::build_create_market_transaction(&accounts, &wallet.pubkey(), args, &options)?;
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    transaction.sign(&[&wallet, &mint_keypair], recent_blockhash);
+
+    let signature = rpc_client.send_and_confirm_transaction(&transaction).map_err(error::decode_client_error)?;
+    println!("Signature : {}", signature);
+
+    Ok(())
+}
+
+fn list_creator_fees() -> Result<()> {
+    let wallet = load_wallet()?;
+    let rpc_client = load_rpc_client()?;
+
+    let markets = fees::fetch_creator_markets(&rpc_client, &wallet.pubkey())?;
+    for fees in fees::fetch_creator_fees(&rpc_client, &markets)? {
+        match &fees.fee_reserve {
+            Some(fee_reserve) => println!(
+                "{} : {} of {} in fee reserve {}, updated at {}",
+                fees.market_address, fees.claimable, fees.market.token_mint1, fee_reserve.address, fee_reserve.last_update
+            ),
+            None => println!("{} : {} of {}", fees.market_address, fees.claimable, fees.market.token_mint1),
+        }
+    }
+
+    Ok(())
+}
+
+fn claim_creator_fees(
+    market_addresses: &[String],
+    all: bool,
+    min_amount: u64,
+    priority_fee_micro_lamports: Option<u64>,
+) -> Result<()> {
+    let wallet = load_wallet()?;
+    let rpc_client = load_rpc_client()?;
+
+    let markets = if all {
+        fees::fetch_creator_markets(&rpc_client, &wallet.pubkey())?
+    } else {
+        if market_addresses.is_empty() {
+            return Err(anyhow::anyhow!("No market to claim, pass their addresses or --all"));
+        }
+        let addresses = market_addresses
+            .iter()
+            .map(|address| server::parse_pubkey(address))
+            .collect::<Result<Vec<_>>>()?;
+        let accounts = rpc_client.get_multiple_accounts(&addresses)?;
+
+        addresses
+            .iter()
+            .zip(accounts)
+            .map(|(address, account)| {
+                let market = market::Market::from_bytes(&account.with_context(|| format!("Market {} not found", address))?.data)?;
+                if market.creator != wallet.pubkey() {
+                    return Err(anyhow::anyhow!("Market {} was created by {}", address, market.creator));
+                }
+                Ok((*address, market))
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let mut claims = fees::fetch_creator_fees(&rpc_client, &markets)?;
+    claims.retain(|fees| fees.claimable > 0 && fees.claimable >= min_amount);
+    if claims.is_empty() {
+        println!("Nothing to claim");
+        return Ok(());
+    }
+
+    let options = swap::SwapOptions {
+        compute_unit_limit: None,
+        priority_fee_micro_lamports,
+    };
+    let transactions = fees::build_claim_transactions(&claims, &wallet.pubkey(), &options)?;
+    println!("Claiming {} markets in {} transactions", claims.len(), transactions.len());
+
+    for mut transaction in transactions {
+        let recent_blockhash = rpc_client.get_latest_blockhash()?;
+        transaction.sign(&[&wallet], recent_blockhash);
+        let signature = rpc_client.send_and_confirm_transaction(&transaction).map_err(error::decode_client_error)?;
+        println!("Signature : {}", signature);
+    }
+
+    Ok(())
+}
+
+fn print_curve_design(design: &design::CurveDesign, points: usize) -> Result<()> {
+    let curve = design::design_curve(design, points)?;
+    let settings = &curve.settings;
+
+    let output = json!({
+        "settings": {
+            "max_supply": settings.max_supply,
+            "sqrt_price_a_x96": settings.sqrt_price_a_x96.to_string(),
+            "sqrt_price_b_x96": settings.sqrt_price_b_x96.to_string(),
+            "liquidity_a": settings.liquidity_a.to_string(),
+            "liquidity_b": settings.liquidity_b.to_string(),
+            "fee": settings.fee,
+        },
+        "end_sqrt_price_x96": curve.end_sqrt_price_x96.to_string(),
+        "raise_a": curve.raise_a,
+        "total_raise": curve.total_raise,
+        "curve": curve.curve.iter().map(|point| json!({
+            "sold": point.sold,
+            "sqrt_price_x96": point.sqrt_price_x96.to_string(),
+            "price": price::sqrt_price_x96_to_price(point.sqrt_price_x96),
+            "raised": point.raised,
+        })).collect::<Vec<_>>(),
+    });
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
+
+fn print_backtest(events: Option<&str>, fixtures_dir: Option<&str>, strategy: &mut impl backtest::Strategy) -> Result<()> {
+    let events = match (events, fixtures_dir) {
+        (Some(events), _) => backtest::load_events(std::path::Path::new(events))?,
+        (None, Some(dir)) => backtest::events_from_fixtures(&fixtures::load_fixtures(std::path::Path::new(dir))?),
+        (None, None) => return Err(anyhow::anyhow!("No swaps to replay, pass --events or --fixtures")),
+    };
+
+    let report = backtest::run_backtest(&events, strategy)?;
+    println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+
+    Ok(())
+}
+
+async fn record_fixtures(market: &str, count: usize, dir: &str) -> Result<()> {
+    let rpc_client = load_nonblocking_rpc_client()?;
+    let market_address = market.parse::<Pubkey>().ok().context("Invalid market address")?;
+
+    println!("Watching market {} for {} swaps", market_address, count);
+    fixtures::watch_fixtures(&rpc_client, &market_address, count, fixtures::DEFAULT_WATCH_INTERVAL, |fixture| {
+        let path = fixture.write(std::path::Path::new(dir))?;
+        println!("Recorded {}", path.display());
+
+        // A divergence is what the vector is recorded for, show it right away
+        let quote = swap::quote_swap(&fixture.market()?, &fixture.parameters)?;
+        println!("Observed : in {} out {} sqrt price {:?}", fixture.amount_in, fixture.amount_out, fixture.sqrt_price_x96);
+        println!("Quoted   : in {} out {} sqrt price {}", quote.amount_in, quote.amount_out, quote.next_sqrt_price);
+
+        Ok(())
+    })
+    .await
+}
+
+fn check_fixtures(dir: &str) -> Result<()> {
+    let fixtures = fixtures::load_fixtures(std::path::Path::new(dir))?;
+    let mut divergences = 0;
+
+    for fixture in fixtures.iter() {
+        let quote = swap::quote_swap(&fixture.market()?, &fixture.parameters)?;
+        let matches = quote.amount_in == fixture.amount_in
+            && quote.amount_out == fixture.amount_out
+            && fixture.sqrt_price_x96.is_none_or(|sqrt_price_x96| sqrt_price_x96 == quote.next_sqrt_price);
+
+        if !matches {
+            divergences += 1;
+            println!("{} diverges", fixture.signature);
+            println!("  Observed : in {} out {} sqrt price {:?}", fixture.amount_in, fixture.amount_out, fixture.sqrt_price_x96);
+            println!("  Quoted   : in {} out {} sqrt price {}", quote.amount_in, quote.amount_out, quote.next_sqrt_price);
+        }
+    }
+
+    println!("{} fixtures, {} divergences", fixtures.len(), divergences);
+    if divergences > 0 {
+        return Err(anyhow::anyhow!("Quote diverges from {} recorded swaps", divergences));
+    }
+
+    Ok(())
+}
+
+async fn run_sample() {
+
     let sol_mint="So11111111111111111111111111111111111111112";
 
     let jito_tip_accounts = ["ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt","3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT","HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe","DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL","Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY","DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh","ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49","96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"];
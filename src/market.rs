@@ -11,14 +11,32 @@ use borsh::BorshSerialize;
 use solana_sdk::pubkey::Pubkey;
 
 pub const TOKENMILL_PROGRAM: &str = "JoeGXemoPqPeGPEXA3Z3UbjoPoGqqfbg8PD58M7Rqj2";
+// Anchor discriminator of the market account, `sha256("account:Market")[..8]`
+pub const MARKET_DISCRIMINATOR: [u8; 8] = [219, 190, 213, 55, 0, 227, 198, 154];
 
 #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MarketSettings {
     pub max_supply: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
     pub sqrt_price_a_x96: u128,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
     pub sqrt_price_b_x96: u128,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
     pub liquidity_a: u128,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
     pub liquidity_b: u128,
     pub fee: u32,
 }
@@ -37,6 +55,10 @@ pub struct Market {
         serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
     )]
     pub creator: Pubkey,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<Option<serde_with::DisplayFromStr>>")
+    )]
     pub swap_authority: Option<Pubkey>,
     #[cfg_attr(
         feature = "serde",
@@ -58,9 +80,17 @@ pub struct Market {
         serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
     )]
     pub reserve1: Pubkey,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<Option<serde_with::DisplayFromStr>>")
+    )]
     pub fee_reserve: Option<Pubkey>,
     pub fee_reserve_last_update: i64,
     pub settings: MarketSettings,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
     pub sqrt_price_x96: u128,
     pub bump: [u8; 1],
 }
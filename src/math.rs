@@ -1,30 +1,39 @@
-use anyhow::{Result, anyhow};
 use ruint::aliases::{U256, U512};
 
-pub fn mul_div(x: U256, y: U256, denominator: U256) -> Result<u128> {
-    if denominator.is_zero() {
-        return Err( anyhow!("DivisionByZero"));
-    }
-
-    let x = U512::from(x);
-    let y = U512::from(y);
-    let denominator = U512::from(denominator);
+use crate::error::SwapMathError;
 
-    let prod = x.wrapping_mul(y);
+// The product fits in 256 bits for any realistic price and liquidity,
+// the 512 bits division is only needed past that and is several times slower
 
-    let (quotient, _) = prod.div_rem(denominator);
+pub fn mul_div(x: U256, y: U256, denominator: U256) -> Result<u128, SwapMathError> {
+    if denominator.is_zero() {
+        return Err(SwapMathError::DivisionByZero);
+    }
 
-    quotient
-        .try_into()
-        .map_err(|_| anyhow!("AmountOverflow"))
+    match x.checked_mul(y) {
+        Some(prod) => (prod / denominator).try_into(),
+        None => (U512::from(x).wrapping_mul(U512::from(y)) / U512::from(denominator)).try_into(),
+    }
+    .map_err(|_| SwapMathError::AmountOverflow)
 }
 
-pub fn mul_div_round_up(x: U256, y: U256, denominator: U256) -> Result<u128> {
-    let result = mul_div(x, y, denominator)?;
+pub fn mul_div_round_up(x: U256, y: U256, denominator: U256) -> Result<u128, SwapMathError> {
+    if denominator.is_zero() {
+        return Err(SwapMathError::DivisionByZero);
+    }
 
-    if (x % denominator).is_zero() {
-        Ok(result)
-    } else {
-        Ok(result + 1)
+    // Rounds up whenever the product itself isn't a multiple of the denominator
+    // Adding one can't overflow, as the quotient is then strictly below the product
+    match x.checked_mul(y) {
+        Some(prod) => {
+            let (quotient, remainder) = prod.div_rem(denominator);
+            (quotient + U256::from(!remainder.is_zero() as u8)).try_into()
+        }
+        None => {
+            let (quotient, remainder) =
+                U512::from(x).wrapping_mul(U512::from(y)).div_rem(U512::from(denominator));
+            (quotient + U512::from(!remainder.is_zero() as u8)).try_into()
+        }
     }
-}
\ No newline at end of file
+    .map_err(|_| SwapMathError::AmountOverflow)
+}
@@ -0,0 +1,209 @@
+use anyhow::{anyhow, Context, Result};
+use ruint::aliases::U512;
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+use solana_account_decoder::UiAccountData;
+use solana_client::{rpc_client::RpcClient, rpc_request::TokenAccountsFilter, rpc_response::RpcKeyedAccount};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::fees::MAX_MULTIPLE_ACCOUNTS;
+use crate::market::Market;
+use crate::price::sqrt_price_x96_to_price;
+use crate::swap::{quote_swap, SwapParameters, MAX_BPS};
+use crate::swap_math::SQRT_PRICE_SHIFT;
+
+// A token account of the wallet, from either token program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenHolding {
+    pub address: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub amount: u64,
+    // Rent held by the account, returned when it's closed
+    pub lamports: u64,
+}
+
+// Every token account owned by `owner` under both token programs, sorted by mint
+pub fn fetch_token_holdings(rpc_client: &RpcClient, owner: &Pubkey) -> Result<Vec<TokenHolding>> {
+    let mut holdings = vec![];
+
+    for token_program in [spl_token::id(), spl_token_2022::id()] {
+        let accounts = rpc_client
+            .get_token_accounts_by_owner(owner, TokenAccountsFilter::ProgramId(token_program))
+            .with_context(|| format!("Failed to fetch the token accounts of {}", owner))?;
+
+        for account in accounts {
+            holdings.push(parse_token_holding(&account, &token_program)?);
+        }
+    }
+    holdings.sort_by_key(|holding| (holding.mint, holding.address));
+
+    Ok(holdings)
+}
+
+// Token accounts come parsed by the RPC node, as getTokenAccountsByOwner returns them
+pub fn parse_token_holding(account: &RpcKeyedAccount, token_program: &Pubkey) -> Result<TokenHolding> {
+    let address = Pubkey::from_str(&account.pubkey).map_err(|_| anyhow!("Invalid token account: {}", account.pubkey))?;
+    let UiAccountData::Json(data) = &account.account.data else {
+        return Err(anyhow!("Token account {} isn't parsed", address));
+    };
+    let info = &data.parsed["info"];
+
+    let mint = info["mint"]
+        .as_str()
+        .and_then(|mint| Pubkey::from_str(mint).ok())
+        .ok_or(anyhow!("Invalid mint of token account {}", address))?;
+    let amount = info["tokenAmount"]["amount"]
+        .as_str()
+        .and_then(|amount| amount.parse().ok())
+        .ok_or(anyhow!("Invalid amount of token account {}", address))?;
+
+    Ok(TokenHolding {
+        address,
+        mint,
+        token_program: *token_program,
+        amount,
+        lamports: account.account.lamports,
+    })
+}
+
+// Markets of the given mints as token 0, None for the mints TokenMill doesn't trade
+pub fn fetch_token_markets(rpc_client: &RpcClient, mints: &[Pubkey]) -> Result<Vec<Option<(Pubkey, Market)>>> {
+    let addresses = mints.iter().map(|mint| Market::find_pda(mint).0).collect::<Vec<_>>();
+    let mut accounts = vec![];
+    for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        accounts.extend(rpc_client.get_multiple_accounts(chunk)?);
+    }
+
+    addresses
+        .into_iter()
+        .zip(accounts)
+        .map(|(address, account)| match account {
+            Some(account) => Ok(Some((address, Market::from_bytes(&account.data)?))),
+            None => Ok(None),
+        })
+        .collect()
+}
+
+// Worth of a balance of token 0, in raw token 1 units
+#[derive(Clone, Debug, PartialEq)]
+pub struct Valuation {
+    // At the current price, as if the balance could be sold without moving it
+    pub spot_value: u64,
+    // What selling the whole balance returns now, fees included
+    pub liquidation_value: u64,
+    // Of the liquidation value below the spot value, in bps
+    pub price_impact_bps: f64,
+}
+
+impl Valuation {
+    pub fn new(spot_value: u64, liquidation_value: u64) -> Self {
+        let price_impact_bps = if spot_value > 0 {
+            spot_value.saturating_sub(liquidation_value) as f64 / spot_value as f64 * MAX_BPS as f64
+        } else {
+            0.0
+        };
+
+        Self { spot_value, liquidation_value, price_impact_bps }
+    }
+}
+
+pub fn value_position(market: &Market, amount: u64) -> Result<Valuation> {
+    let sqrt_price = U512::from(market.sqrt_price_x96);
+    let spot_value = (U512::from(amount) * sqrt_price * sqrt_price) >> (2 * SQRT_PRICE_SHIFT);
+    let spot_value = u64::try_from(spot_value).unwrap_or(u64::MAX);
+
+    let liquidation_value = if amount > 0 {
+        quote_swap(market, &SwapParameters::SellExactIn(amount, 0))?.amount_out
+    } else {
+        0
+    };
+
+    Ok(Valuation::new(spot_value, liquidation_value))
+}
+
+#[derive(Clone, Debug)]
+pub struct PortfolioPosition {
+    pub holding: TokenHolding,
+    pub market: Option<(Pubkey, Market)>,
+    // Only for holdings of a TokenMill token
+    pub valuation: Option<Valuation>,
+    // Why a holding of a TokenMill token couldn't be valued
+    pub valuation_error: Option<String>,
+}
+
+impl PortfolioPosition {
+    // A position that can't be valued, e.g. too large to sell at once, keeps the error instead of failing
+    pub fn new(holding: TokenHolding, market: Option<(Pubkey, Market)>) -> Self {
+        let (valuation, valuation_error) = match market.as_ref().map(|(_, market)| value_position(market, holding.amount)) {
+            Some(Ok(valuation)) => (Some(valuation), None),
+            Some(Err(err)) => (None, Some(err.to_string())),
+            None => (None, None),
+        };
+
+        Self { holding, market, valuation, valuation_error }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (market_address, token_mint1, price) = match &self.market {
+            Some((address, market)) => (
+                Some(address.to_string()),
+                Some(market.token_mint1.to_string()),
+                Some(sqrt_price_x96_to_price(market.sqrt_price_x96)),
+            ),
+            None => (None, None, None),
+        };
+
+        json!({
+            "account": self.holding.address.to_string(),
+            "mint": self.holding.mint.to_string(),
+            "token_program": self.holding.token_program.to_string(),
+            "amount": self.holding.amount,
+            "market": market_address,
+            "token_mint1": token_mint1,
+            "price": price,
+            "spot_value": self.valuation.as_ref().map(|valuation| valuation.spot_value),
+            "liquidation_value": self.valuation.as_ref().map(|valuation| valuation.liquidation_value),
+            "price_impact_bps": self.valuation.as_ref().map(|valuation| valuation.price_impact_bps),
+            "valuation_error": self.valuation_error,
+        })
+    }
+}
+
+pub fn fetch_portfolio(rpc_client: &RpcClient, owner: &Pubkey) -> Result<Vec<PortfolioPosition>> {
+    let holdings = fetch_token_holdings(rpc_client, owner)?;
+    let mints = holdings.iter().map(|holding| holding.mint).collect::<Vec<_>>();
+    let markets = fetch_token_markets(rpc_client, &mints)?;
+
+    Ok(holdings
+        .into_iter()
+        .zip(markets)
+        .map(|(holding, market)| PortfolioPosition::new(holding, market))
+        .collect())
+}
+
+// Values by quote token, as positions of markets quoted in different tokens can't be added up
+pub fn portfolio_totals(positions: &[PortfolioPosition]) -> Vec<(Pubkey, Valuation)> {
+    let mut totals: Vec<(Pubkey, u64, u64)> = vec![];
+
+    for position in positions {
+        let (Some((_, market)), Some(valuation)) = (&position.market, &position.valuation) else {
+            continue;
+        };
+        match totals.iter_mut().find(|(token_mint1, ..)| *token_mint1 == market.token_mint1) {
+            Some((_, spot_value, liquidation_value)) => {
+                *spot_value = spot_value.saturating_add(valuation.spot_value);
+                *liquidation_value = liquidation_value.saturating_add(valuation.liquidation_value);
+            }
+            None => totals.push((market.token_mint1, valuation.spot_value, valuation.liquidation_value)),
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|(token_mint1, spot_value, liquidation_value)| {
+            (token_mint1, Valuation::new(spot_value, liquidation_value))
+        })
+        .collect()
+}
@@ -0,0 +1,32 @@
+use crate::market::Market;
+use crate::swap_math::SQRT_PRICE_SHIFT;
+
+// Price of token 0 in token 1, in raw units (not adjusted for decimals)
+pub fn sqrt_price_x96_to_price(sqrt_price_x96: u128) -> f64 {
+    let sqrt_price = sqrt_price_x96 as f64 / 2f64.powi(SQRT_PRICE_SHIFT as i32);
+
+    sqrt_price * sqrt_price
+}
+
+pub fn is_phase_a(market: &Market, sqrt_price_x96: u128) -> bool {
+    sqrt_price_x96 < market.settings.sqrt_price_b_x96
+}
+
+// Inverse of `sqrt_price_x96_to_price`, None when the price doesn't map to a valid sqrt price
+pub fn price_to_sqrt_price_x96(price: f64) -> Option<u128> {
+    if !price.is_finite() || price <= 0.0 {
+        return None;
+    }
+
+    let sqrt_price_x96 = price.sqrt() * 2f64.powi(SQRT_PRICE_SHIFT as i32);
+    if sqrt_price_x96 >= u128::MAX as f64 {
+        return None;
+    }
+
+    Some(sqrt_price_x96 as u128)
+}
+
+// Raw price of a price expressed in whole tokens, e.g. 0.0001 SOL per token
+pub fn ui_price_to_price(ui_price: f64, decimals0: u8, decimals1: u8) -> f64 {
+    ui_price * 10f64.powi(i32::from(decimals1) - i32::from(decimals0))
+}
@@ -1,144 +1,314 @@
-use anyhow::Result;
-
 // mod swap_math;
 // mod market;
 
-use crate::swap_math::get_delta_amounts;
-use crate::market::Market;
+use crate::error::SwapMathError;
+use crate::swap_math::Liquidity;
+use crate::market::{Market, MarketSettings};
+
+type Result<T> = std::result::Result<T, SwapMathError>;
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Quote {
     pub amount_in: u64,
     pub amount_out: u64,
     pub fee_amount_token_in: u64,
     pub fee_amount_token_1: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
     pub next_sqrt_price: u128,
+    // Segments of the curve the swap went through, in order, one per phase
+    // Only filled by `quote_with_legs`, empty otherwise
+    // For sells, the swap converting the fee to token 1 isn't part of them, so it moves the price past the last leg
+    pub legs: Vec<QuoteLeg>,
+    // Whether the swap went past `sqrt_price_b_x96` into the other phase
+    pub crossed_phase_boundary: bool,
+    // Whether the swap stopped at `sqrt_price_limit` before being filled
+    pub hit_sqrt_price_limit: bool,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum Phase {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Phase {
     A,
     B,
 }
 
+// Part of a swap within a single phase, `amount_in` excludes the fee
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuoteLeg {
+    pub phase: Phase,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub sqrt_price_start: u128,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub sqrt_price_end: u128,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub liquidity: u128,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+}
+
+impl QuoteLeg {
+    // Neither moved the price nor traded anything
+    pub fn is_empty(&self) -> bool {
+        self.sqrt_price_start == self.sqrt_price_end
+            && self.amount_in == 0
+            && self.amount_out == 0
+            && self.fee_amount == 0
+    }
+}
+
 pub fn quote(
     market: &Market,
     zero_for_one: bool,
     delta_amount: i64,
     sqrt_price_limit: u128,
 ) -> Result<Quote> {
-    let (mut next_sqrt_price, amount_in, amount_out, fee_amount_token_in) =
-        get_delta_amounts_from_dual_pool(
-            market,
-            market.sqrt_price_x96,
-            zero_for_one,
-            delta_amount,
-            sqrt_price_limit,
-            market.settings.fee,
-        )?;
-
-    // Get fee as token 1
-    let fee_amount_token_1 = if zero_for_one {
-        let (sqrt_price_after_fee_swap, _, fee_amount, _) = get_delta_amounts_from_dual_pool(
-            market,
-            next_sqrt_price,
-            true,
-            i64::try_from(fee_amount_token_in)?,
-            market.settings.sqrt_price_a_x96,
-            0,
-        )?;
-
-        next_sqrt_price = sqrt_price_after_fee_swap;
-
-        fee_amount
-    } else {
-        fee_amount_token_in
-    };
-
-    Ok(Quote {
-        amount_in,
-        amount_out,
-        fee_amount_token_in,
-        fee_amount_token_1,
-        next_sqrt_price,
-    })
+    PreparedMarket::new(&market.settings).quote(
+        market.sqrt_price_x96,
+        zero_for_one,
+        delta_amount,
+        sqrt_price_limit,
+    )
 }
 
-fn get_delta_amounts_from_dual_pool(
+// Same as `quote`, with the legs of the swap filled in
+pub fn quote_with_legs(
     market: &Market,
-    current_sqrt_price: u128,
     zero_for_one: bool,
-    mut delta_amount: i64,
+    delta_amount: i64,
     sqrt_price_limit: u128,
-    fee: u32,
-) -> Result<(u128, u64, u64, u64)> {
-    let phase = if current_sqrt_price < market.settings.sqrt_price_b_x96 {
-        Phase::A
-    } else {
-        Phase::B
-    };
-
-    let (first_l, second_l) = match phase {
-        Phase::A => (market.settings.liquidity_a, market.settings.liquidity_b),
-        Phase::B => (market.settings.liquidity_b, market.settings.liquidity_a),
-    };
-
-    // First pool
-    let first_sqrt_price_target = if zero_for_one == false {
-        if phase == Phase::A {
-            sqrt_price_limit.min(market.settings.sqrt_price_b_x96)
+) -> Result<Quote> {
+    PreparedMarket::new(&market.settings).quote_with_legs(
+        market.sqrt_price_x96,
+        zero_for_one,
+        delta_amount,
+        sqrt_price_limit,
+    )
+}
+
+// Settings of a market widened once, to quote it many times in a row, e.g. from a routing loop
+// Only the price changes between swaps, so it's passed to every quote
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreparedMarket {
+    pub sqrt_price_a_x96: u128,
+    pub sqrt_price_b_x96: u128,
+    pub liquidity_a: Liquidity,
+    pub liquidity_b: Liquidity,
+    pub fee: u32,
+}
+
+impl PreparedMarket {
+    pub fn new(settings: &MarketSettings) -> Self {
+        Self {
+            sqrt_price_a_x96: settings.sqrt_price_a_x96,
+            sqrt_price_b_x96: settings.sqrt_price_b_x96,
+            liquidity_a: Liquidity::new(settings.liquidity_a),
+            liquidity_b: Liquidity::new(settings.liquidity_b),
+            fee: settings.fee,
+        }
+    }
+
+    // Leaves `legs` empty, so that quoting doesn't allocate
+    pub fn quote(
+        &self,
+        sqrt_price_x96: u128,
+        zero_for_one: bool,
+        delta_amount: i64,
+        sqrt_price_limit: u128,
+    ) -> Result<Quote> {
+        self.quote_into(sqrt_price_x96, zero_for_one, delta_amount, sqrt_price_limit, None)
+    }
+
+    pub fn quote_with_legs(
+        &self,
+        sqrt_price_x96: u128,
+        zero_for_one: bool,
+        delta_amount: i64,
+        sqrt_price_limit: u128,
+    ) -> Result<Quote> {
+        let mut legs = Vec::with_capacity(2);
+        let mut quote = self.quote_into(sqrt_price_x96, zero_for_one, delta_amount, sqrt_price_limit, Some(&mut legs))?;
+        quote.legs = legs;
+
+        Ok(quote)
+    }
+
+    fn quote_into(
+        &self,
+        sqrt_price_x96: u128,
+        zero_for_one: bool,
+        delta_amount: i64,
+        sqrt_price_limit: u128,
+        legs: Option<&mut Vec<QuoteLeg>>,
+    ) -> Result<Quote> {
+        let (mut next_sqrt_price, amount_in, amount_out, fee_amount_token_in, crossed_phase_boundary) = self
+            .get_delta_amounts_from_dual_pool(
+                sqrt_price_x96,
+                zero_for_one,
+                delta_amount,
+                sqrt_price_limit,
+                self.fee,
+                legs,
+            )?;
+        let filled = if delta_amount.is_positive() { amount_in } else { amount_out };
+        let hit_sqrt_price_limit = next_sqrt_price == sqrt_price_limit && filled < delta_amount.unsigned_abs();
+
+        // Get fee as token 1
+        let fee_amount_token_1 = if zero_for_one {
+            let (sqrt_price_after_fee_swap, _, fee_amount, _, _) = self.get_delta_amounts_from_dual_pool(
+                next_sqrt_price,
+                true,
+                i64::try_from(fee_amount_token_in).map_err(|_| SwapMathError::FeeAmountOverflow)?,
+                self.sqrt_price_a_x96,
+                0,
+                None,
+            )?;
+
+            next_sqrt_price = sqrt_price_after_fee_swap;
+
+            fee_amount
         } else {
+            fee_amount_token_in
+        };
+
+        Ok(Quote {
+            amount_in,
+            amount_out,
+            fee_amount_token_in,
+            fee_amount_token_1,
+            next_sqrt_price,
+            legs: vec![],
+            crossed_phase_boundary,
+            hit_sqrt_price_limit,
+        })
+    }
+
+    fn get_delta_amounts_from_dual_pool(
+        &self,
+        current_sqrt_price: u128,
+        zero_for_one: bool,
+        mut delta_amount: i64,
+        sqrt_price_limit: u128,
+        fee: u32,
+        mut legs: Option<&mut Vec<QuoteLeg>>,
+    ) -> Result<(u128, u64, u64, u64, bool)> {
+        let phase = if current_sqrt_price < self.sqrt_price_b_x96 {
+            Phase::A
+        } else {
+            Phase::B
+        };
+
+        let (first_l, second_l, second_phase) = match phase {
+            Phase::A => (&self.liquidity_a, &self.liquidity_b, Phase::B),
+            Phase::B => (&self.liquidity_b, &self.liquidity_a, Phase::A),
+        };
+
+        // First pool
+        let first_sqrt_price_target = if zero_for_one == false {
+            if phase == Phase::A {
+                sqrt_price_limit.min(self.sqrt_price_b_x96)
+            } else {
+                sqrt_price_limit
+            }
+        } else if phase == Phase::A {
             sqrt_price_limit
+        } else {
+            sqrt_price_limit.max(self.sqrt_price_b_x96)
+        };
+
+        let (mut new_sqrt_price, mut amount_in, mut amount_out, mut fee_amount) =
+            first_l.get_delta_amounts(current_sqrt_price, first_sqrt_price_target, delta_amount, fee)?;
+        let first_leg = QuoteLeg {
+            phase,
+            sqrt_price_start: current_sqrt_price,
+            sqrt_price_end: new_sqrt_price,
+            liquidity: first_l.value,
+            amount_in,
+            amount_out,
+            fee_amount,
+        };
+        let mut crossed_phase_boundary = false;
+        if let Some(legs) = legs.as_deref_mut() {
+            legs.push(first_leg.clone());
+        }
+
+        if delta_amount.is_positive() {
+            // Safe cast
+            delta_amount -= (amount_in + fee_amount) as i64;
+        } else {
+            // `amount_out` can be 2^63 when `delta_amount` is i64::MIN
+            delta_amount = delta_amount
+                .checked_add_unsigned(amount_out)
+                .ok_or(SwapMathError::DeltaAmountOverflow)?;
         }
-    } else if phase == Phase::A {
-        sqrt_price_limit
-    } else {
-        sqrt_price_limit.max(market.settings.sqrt_price_b_x96)
-    };
-
-    let (mut new_sqrt_price, mut amount_in, mut amount_out, mut fee_amount) = get_delta_amounts(
-        current_sqrt_price,
-        first_sqrt_price_target,
-        first_l,
-        delta_amount,
-        fee,
-    )?;
-
-    if delta_amount.is_positive() {
-        // Safe cast
-        delta_amount -= (amount_in + fee_amount) as i64;
-    } else {
-        // Safe cast
-        delta_amount += amount_out as i64;
-    }
 
-    // Second pool
-    if delta_amount != 0 && new_sqrt_price != sqrt_price_limit {
-        let (additional_amount_in, additional_amount_out, additional_fee_amount);
+        // Second pool
+        if delta_amount != 0 && new_sqrt_price != sqrt_price_limit {
+            let sqrt_price_start = new_sqrt_price;
+            let (additional_amount_in, additional_amount_out, additional_fee_amount);
 
-        (
-            new_sqrt_price,
-            additional_amount_in,
-            additional_amount_out,
-            additional_fee_amount,
-        ) = get_delta_amounts(
+            (
+                new_sqrt_price,
+                additional_amount_in,
+                additional_amount_out,
+                additional_fee_amount,
+            ) = second_l.get_delta_amounts(new_sqrt_price, sqrt_price_limit, delta_amount, fee)?;
+
+            let second_leg = QuoteLeg {
+                phase: second_phase,
+                sqrt_price_start,
+                sqrt_price_end: new_sqrt_price,
+                liquidity: second_l.value,
+                amount_in: additional_amount_in,
+                amount_out: additional_amount_out,
+                fee_amount: additional_fee_amount,
+            };
+            // A swap starting right on `sqrt_price_b_x96` has nothing to do in the phase it's said to be in,
+            // it then only trades in the other one
+            if !second_leg.is_empty() {
+                crossed_phase_boundary = !first_leg.is_empty();
+                if let Some(legs) = legs {
+                    if !crossed_phase_boundary {
+                        legs.clear();
+                    }
+                    legs.push(second_leg);
+                }
+            }
+
+            amount_in = amount_in
+                .checked_add(additional_amount_in)
+                .ok_or(SwapMathError::AmountInOverflow)?;
+            amount_out = amount_out
+                .checked_add(additional_amount_out)
+                .ok_or(SwapMathError::AmountOutOverflow)?;
+            fee_amount = fee_amount
+                .checked_add(additional_fee_amount)
+                .ok_or(SwapMathError::FeeAmountOverflow)?;
+        }
+
+        Ok((
             new_sqrt_price,
-            sqrt_price_limit,
-            second_l,
-            delta_amount,
-            fee,
-        )?;
-
-        amount_in += additional_amount_in;
-        amount_out += additional_amount_out;
-        fee_amount += additional_fee_amount;
+            amount_in
+                .checked_add(fee_amount)
+                .ok_or(SwapMathError::AmountInOverflow)?,
+            amount_out,
+            fee_amount,
+            crossed_phase_boundary,
+        ))
     }
-
-    Ok((
-        new_sqrt_price,
-        amount_in + fee_amount,
-        amount_out,
-        fee_amount,
-    ))
-}
\ No newline at end of file
+}
@@ -0,0 +1,259 @@
+use anyhow::{anyhow, Result};
+
+use reqwest::Client;
+use serde_json::{json, Value};
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::TransactionError,
+};
+
+use crate::authority::request_cosign;
+use crate::error::{describe_client_error, describe_transaction_error, TokenMillError};
+use crate::market::Market;
+use crate::swap::{build_swap_transaction, quote_swap, QuotedSwap, SwapAccounts, SwapOptions, SwapParameters};
+use crate::tracker::{track_swap, Confirmation, FillReport, SentSwap};
+
+pub const DEFAULT_MAX_ATTEMPTS: usize = 3;
+// Custom error of the token and system programs when an account can't cover a transfer
+const INSUFFICIENT_FUNDS_ERROR: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    Slippage,
+    // Also when the blockhash expired before the transaction landed
+    BlockhashNotFound,
+    AccountInUse,
+    InsufficientFunds,
+    Other,
+}
+
+impl FailureKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Slippage => "slippage",
+            Self::BlockhashNotFound => "blockhash_not_found",
+            Self::AccountInUse => "account_in_use",
+            Self::InsufficientFunds => "insufficient_funds",
+            Self::Other => "other",
+        }
+    }
+
+    // Whether the same swap, re-quoted and sent with a fresh blockhash, can land
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Slippage | Self::BlockhashNotFound | Self::AccountInUse)
+    }
+}
+
+// TokenMill errors start at 6000, so the low custom codes can only come from the token or system programs
+pub fn classify_transaction_error(err: &TransactionError) -> FailureKind {
+    match err {
+        _ if TokenMillError::from_transaction_error(err) == Some(TokenMillError::SlippageExceeded) => {
+            FailureKind::Slippage
+        }
+        TransactionError::InstructionError(_, InstructionError::Custom(INSUFFICIENT_FUNDS_ERROR))
+        | TransactionError::InsufficientFundsForFee
+        | TransactionError::InsufficientFundsForRent { .. } => FailureKind::InsufficientFunds,
+        TransactionError::BlockhashNotFound => FailureKind::BlockhashNotFound,
+        TransactionError::AccountInUse => FailureKind::AccountInUse,
+        _ => FailureKind::Other,
+    }
+}
+
+// Sending fails with the transaction error when the preflight simulation does
+pub fn classify_client_error(err: &ClientError) -> FailureKind {
+    err.get_transaction_error()
+        .map(|err| classify_transaction_error(&err))
+        .unwrap_or(FailureKind::Other)
+}
+
+// Average price of the swap when it only meets its threshold, in raw token 1 units per token 0 unit
+// None when the threshold lets a buy pay any price
+pub fn worst_price(parameters: &SwapParameters) -> Option<f64> {
+    let (amount0, amount1) = match *parameters {
+        SwapParameters::BuyExactIn(amount, threshold) => (threshold, amount),
+        SwapParameters::BuyExactOut(amount, threshold) => (amount, threshold),
+        SwapParameters::SellExactIn(amount, threshold) => (amount, threshold),
+        SwapParameters::SellExactOut(amount, threshold) => (threshold, amount),
+    };
+
+    (amount0 > 0).then(|| amount1 as f64 / amount0 as f64)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    pub slippage_bps: u64,
+    // Worst average price accepted, the highest for buys and the lowest for sells, in raw token 1 units per token 0
+    // unit. Checked against the thresholds of every attempt, so re-quotes on a moving market can't go past it
+    pub price_limit: Option<f64>,
+}
+
+impl RetryPolicy {
+    // Thresholds of the swap on the market as it is now
+    pub fn requote(&self, market: &Market, parameters: &SwapParameters) -> Result<QuotedSwap> {
+        let quote = quote_swap(market, parameters)?;
+        let parameters = parameters.with_slippage(&quote, self.slippage_bps)?;
+
+        if let Some(price_limit) = self.price_limit {
+            let within_limit = match worst_price(&parameters) {
+                Some(price) if parameters.zero_for_one() => price >= price_limit,
+                Some(price) => price <= price_limit,
+                None => false,
+            };
+            if !within_limit {
+                return Err(anyhow!(
+                    "PriceLimitExceeded: {} {} at {:?}, limit {}",
+                    parameters.side(),
+                    parameters.amount(),
+                    worst_price(&parameters),
+                    price_limit
+                ));
+            }
+        }
+
+        Ok(QuotedSwap { parameters, quote })
+    }
+}
+
+// What a swap is sent with, the thresholds being set on every attempt
+pub struct SwapExecution<'a> {
+    pub market_address: Pubkey,
+    pub accounts: SwapAccounts,
+    pub parameters: SwapParameters,
+    pub options: SwapOptions,
+    // Signs and pays for the swap
+    pub wallet: &'a Keypair,
+    // On restricted markets, either the authority key or the service co-signing with it
+    pub swap_authority: Option<&'a Keypair>,
+    pub cosigner_url: Option<&'a str>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttemptOutcome {
+    Filled(Box<FillReport>),
+    Failed { kind: FailureKind, error: String },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attempt {
+    // Starting from 1
+    pub number: usize,
+    // Of the market the attempt was quoted on
+    pub sqrt_price_x96: u128,
+    pub swap: QuotedSwap,
+    pub signature: Signature,
+    pub outcome: AttemptOutcome,
+}
+
+impl Attempt {
+    pub fn to_json(&self) -> Value {
+        let (kind, error, fill) = match &self.outcome {
+            AttemptOutcome::Filled(report) => (None, None, Some(report.to_json())),
+            AttemptOutcome::Failed { kind, error } => (Some(kind.as_str()), Some(error.as_str()), None),
+        };
+
+        json!({
+            "attempt": self.number,
+            "sqrt_price_x96": self.sqrt_price_x96.to_string(),
+            "side": self.swap.parameters.side(),
+            "mode": self.swap.parameters.mode(),
+            "amount": self.swap.parameters.amount(),
+            "threshold": self.swap.parameters.threshold(),
+            "signature": self.signature.to_string(),
+            "failure": kind,
+            "error": error,
+            "fill": fill,
+        })
+    }
+}
+
+// Sends the swap until it fills, the market being refetched and the swap re-quoted before every attempt
+// Stops on failures a retry can't fix, once the re-quote goes past the price limit, or after `max_attempts`
+// Every attempt is passed to `on_attempt` as it ends
+pub async fn execute_swap(
+    rpc_client: &RpcClient,
+    client: &Client,
+    execution: &SwapExecution<'_>,
+    policy: &RetryPolicy,
+    mut on_attempt: impl FnMut(&Attempt),
+) -> Result<FillReport> {
+    let user = execution.wallet.pubkey();
+    let mut failure = None;
+
+    for number in 1..=policy.max_attempts {
+        let market = Market::from_bytes(&rpc_client.get_account(&execution.market_address).await?.data)?;
+        let swap = policy.requote(&market, &execution.parameters)?;
+
+        let mut transaction =
+            build_swap_transaction(&execution.accounts, &user, &user, &swap.parameters, &execution.options);
+        let (recent_blockhash, last_valid_block_height) =
+            rpc_client.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed()).await?;
+        match execution.cosigner_url {
+            Some(cosigner_url) => {
+                transaction.partial_sign(&[execution.wallet], recent_blockhash);
+                transaction = request_cosign(client, cosigner_url, &transaction).await?;
+            }
+            None => {
+                let mut keypairs = vec![execution.wallet];
+                keypairs.extend(execution.swap_authority);
+                transaction.try_sign(&keypairs, recent_blockhash)?;
+            }
+        }
+
+        let signature = transaction.signatures[0];
+        let outcome = match rpc_client.send_transaction(&transaction).await {
+            Err(err) => AttemptOutcome::Failed {
+                kind: classify_client_error(&err),
+                error: describe_client_error(&err),
+            },
+            Ok(signature) => {
+                let sent = SentSwap {
+                    signature,
+                    market: execution.market_address,
+                    user,
+                    parameters: swap.parameters.clone(),
+                    quote: swap.quote.clone(),
+                    last_valid_block_height,
+                };
+                let report = track_swap(rpc_client, &sent, CommitmentConfig::confirmed()).await?;
+                match &report.confirmation {
+                    Confirmation::Confirmed { .. } => AttemptOutcome::Filled(Box::new(report)),
+                    Confirmation::Failed { err, .. } => AttemptOutcome::Failed {
+                        kind: classify_transaction_error(err),
+                        error: describe_transaction_error(err),
+                    },
+                    Confirmation::Expired => AttemptOutcome::Failed {
+                        kind: FailureKind::BlockhashNotFound,
+                        error: "Blockhash expired before the transaction landed".to_string(),
+                    },
+                }
+            }
+        };
+
+        let attempt = Attempt { number, sqrt_price_x96: market.sqrt_price_x96, swap, signature, outcome };
+        on_attempt(&attempt);
+        match attempt.outcome {
+            AttemptOutcome::Filled(report) => return Ok(*report),
+            AttemptOutcome::Failed { kind, error } => {
+                if !kind.is_retryable() {
+                    return Err(anyhow!("SwapFailed: {} on attempt {}: {}", kind.as_str(), number, error));
+                }
+                failure = Some((kind, error));
+            }
+        }
+    }
+
+    match failure {
+        Some((kind, error)) => Err(anyhow!(
+            "SwapFailed: {} after {} attempts: {}",
+            kind.as_str(),
+            policy.max_attempts,
+            error
+        )),
+        None => Err(anyhow!("SwapFailed: no attempt allowed")),
+    }
+}
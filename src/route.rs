@@ -0,0 +1,272 @@
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    message::Message,
+    pubkey::Pubkey,
+    system_instruction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token::instruction::{close_account, sync_native};
+
+use crate::market::Market;
+use crate::swap::{build_swap_instruction, quote_swap, QuotedSwap, SwapAccounts, SwapOptions, SwapParameters, MAX_BPS};
+use crate::token::{get_associated_token_address, is_native_mint};
+
+pub const DEFAULT_MAX_HOPS: usize = 2;
+
+// A market the router may go through
+#[derive(Clone, Debug)]
+pub struct RouteMarket {
+    pub accounts: SwapAccounts,
+    pub market: Market,
+}
+
+impl RouteMarket {
+    // Sells go from token 0 to token 1, buys the other way around
+    fn mints(&self, zero_for_one: bool) -> (Pubkey, Pubkey) {
+        if zero_for_one {
+            (self.market.token_mint0, self.market.token_mint1)
+        } else {
+            (self.market.token_mint1, self.market.token_mint0)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RouteHop {
+    pub market: RouteMarket,
+    pub zero_for_one: bool,
+}
+
+impl RouteHop {
+    pub fn mint_in(&self) -> Pubkey {
+        self.market.mints(self.zero_for_one).0
+    }
+
+    pub fn mint_out(&self) -> Pubkey {
+        self.market.mints(self.zero_for_one).1
+    }
+
+    fn exact_in(&self, amount: u64, threshold: u64) -> SwapParameters {
+        if self.zero_for_one {
+            SwapParameters::SellExactIn(amount, threshold)
+        } else {
+            SwapParameters::BuyExactIn(amount, threshold)
+        }
+    }
+
+    // Least the hop must spend to return `amount_out` on its market as it is now
+    fn required_amount_in(&self, amount_out: u64) -> Result<u64> {
+        let parameters = if self.zero_for_one {
+            SwapParameters::SellExactOut(amount_out, 0)
+        } else {
+            SwapParameters::BuyExactOut(amount_out, 0)
+        };
+        let quote = quote_swap(&self.market.market, &parameters)?;
+        if quote.amount_out < amount_out {
+            return Err(anyhow!("RouteTooLarge: not enough liquidity to return {} of {}", amount_out, self.mint_out()));
+        }
+
+        Ok(quote.amount_in)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RouteQuote {
+    pub hops: Vec<RouteHop>,
+    // Exact in swaps, in the order they run
+    pub swaps: Vec<QuotedSwap>,
+    pub amount_in: u64,
+    // Quoted output of the last swap, what the route delivers if the markets don't move
+    pub amount_out: u64,
+    // Final output guaranteed by the last swap threshold, the output of the hops chained on their whole expected
+    // outputs less the slippage
+    pub min_amount_out: u64,
+    // Intermediate tokens a hop is expected to return above what the next one spends, left in the user token
+    // accounts, in route order
+    pub leftovers: Vec<(Pubkey, u64)>,
+}
+
+// Every path of at most `max_hops` markets from `input_mint` to `output_mint`, never going through a mint twice
+// Markets with a swap authority are left out, as their swaps need a co-signature
+pub fn find_routes(markets: &[RouteMarket], input_mint: &Pubkey, output_mint: &Pubkey, max_hops: usize) -> Vec<Vec<RouteHop>> {
+    fn visit(
+        markets: &[RouteMarket],
+        mint: Pubkey,
+        output_mint: &Pubkey,
+        max_hops: usize,
+        path: &mut Vec<RouteHop>,
+        visited: &mut HashSet<Pubkey>,
+        routes: &mut Vec<Vec<RouteHop>>,
+    ) {
+        if mint == *output_mint {
+            routes.push(path.clone());
+            return;
+        }
+        if path.len() == max_hops {
+            return;
+        }
+
+        for market in markets.iter().filter(|market| market.market.swap_authority.is_none()) {
+            for zero_for_one in [true, false] {
+                let (mint_in, mint_out) = market.mints(zero_for_one);
+                if mint_in != mint || visited.contains(&mint_out) {
+                    continue;
+                }
+
+                visited.insert(mint_out);
+                path.push(RouteHop { market: market.clone(), zero_for_one });
+                visit(markets, mint_out, output_mint, max_hops, path, visited, routes);
+                path.pop();
+                visited.remove(&mint_out);
+            }
+        }
+    }
+
+    let mut routes = vec![];
+    if input_mint != output_mint {
+        let mut visited = HashSet::from([*input_mint]);
+        visit(markets, *input_mint, output_mint, max_hops, &mut vec![], &mut visited, &mut routes);
+    }
+
+    routes
+}
+
+// Slippage is taken once, on the output of the whole route quoted on expected amounts
+// Each hop spends what the previous one is guaranteed to return, so that the transaction can't run short of the
+// intermediate tokens, and its threshold is the least the hops after it need to meet the final bound
+// The first hop thus takes all the slippage, and a hop doing better than its threshold leaves the excess in the
+// user token account
+pub fn quote_route(hops: &[RouteHop], amount_in: u64, slippage_bps: u64) -> Result<RouteQuote> {
+    if hops.is_empty() {
+        return Err(anyhow!("InvalidRoute: no hop"));
+    }
+    if slippage_bps > MAX_BPS {
+        return Err(anyhow!("InvalidSlippage: {}", slippage_bps));
+    }
+
+    let mut expected_amount_out = amount_in;
+    for hop in hops {
+        expected_amount_out = quote_swap(&hop.market.market, &hop.exact_in(expected_amount_out, 0))?.amount_out;
+    }
+    // Below `expected_amount_out`, so it fits
+    let min_amount_out =
+        (u128::from(expected_amount_out) * u128::from(MAX_BPS - slippage_bps) / u128::from(MAX_BPS)) as u64;
+    if min_amount_out == 0 {
+        return Err(anyhow!("RouteTooSmall: {} of {} returns nothing", amount_in, hops[0].mint_in()));
+    }
+
+    // From the last hop back, what each one must return for the next ones to return `min_amount_out`
+    let mut thresholds = vec![min_amount_out; hops.len()];
+    for (index, hop) in hops.iter().enumerate().skip(1).rev() {
+        thresholds[index - 1] = hop.required_amount_in(thresholds[index])?;
+    }
+
+    let mut swaps: Vec<QuotedSwap> = vec![];
+    let mut leftovers = vec![];
+    let mut amount = amount_in;
+    for (hop, threshold) in hops.iter().zip(thresholds) {
+        if let Some(previous) = swaps.last() {
+            leftovers.push((hop.mint_in(), previous.quote.amount_out - amount));
+        }
+
+        let parameters = hop.exact_in(amount, threshold);
+        let quote = quote_swap(&hop.market.market, &parameters)?;
+        if quote.amount_out < threshold {
+            return Err(anyhow!(
+                "RouteTooSmall: {} of {} returns {}, below the {} the route needs",
+                amount,
+                hop.mint_in(),
+                quote.amount_out,
+                threshold
+            ));
+        }
+
+        amount = threshold;
+        swaps.push(QuotedSwap { parameters, quote });
+    }
+
+    Ok(RouteQuote {
+        hops: hops.to_vec(),
+        amount_in,
+        // At least `min_amount_out`, checked above
+        amount_out: swaps.last().map_or(0, |swap| swap.quote.amount_out),
+        min_amount_out,
+        leftovers,
+        swaps,
+    })
+}
+
+// Route returning the most of `output_mint`
+pub fn best_route(
+    markets: &[RouteMarket],
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount_in: u64,
+    slippage_bps: u64,
+    max_hops: usize,
+) -> Result<RouteQuote> {
+    find_routes(markets, input_mint, output_mint, max_hops)
+        .iter()
+        .filter_map(|hops| quote_route(hops, amount_in, slippage_bps).ok())
+        .max_by_key(|route| route.min_amount_out)
+        .ok_or(anyhow!("NoRoute: from {} to {} within {} hops", input_mint, output_mint, max_hops))
+}
+
+// Compute budget, token accounts of every mint along the route, then the swaps in order
+// SOL is wrapped when it's the input and unwrapped at the end whenever wSOL is part of the route
+pub fn build_route_instructions(route: &RouteQuote, user: &Pubkey, options: &SwapOptions) -> Vec<Instruction> {
+    let mut instructions = vec![];
+
+    if let Some(compute_unit_limit) = options.compute_unit_limit {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit));
+    }
+    if let Some(priority_fee) = options.priority_fee_micro_lamports {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_price(priority_fee));
+    }
+
+    let mut token_accounts = vec![];
+    for hop in route.hops.iter() {
+        let accounts = &hop.market.accounts;
+        for (mint, token_program) in [
+            (accounts.token_mint0, accounts.token_program0),
+            (accounts.token_mint1, accounts.token_program1),
+        ] {
+            if !token_accounts.contains(&(mint, token_program)) {
+                instructions.push(create_associated_token_account_idempotent(user, user, &mint, &token_program));
+                token_accounts.push((mint, token_program));
+            }
+        }
+    }
+
+    let wsol_account = token_accounts
+        .iter()
+        .find(|(mint, _)| is_native_mint(mint))
+        .map(|(mint, token_program)| (get_associated_token_address(user, mint, token_program), *token_program));
+    if let Some((wsol_account, token_program)) = wsol_account {
+        if is_native_mint(&route.hops[0].mint_in()) {
+            instructions.push(system_instruction::transfer(user, &wsol_account, route.amount_in));
+            instructions.push(sync_native(&token_program, &wsol_account).unwrap());
+        }
+    }
+
+    for (hop, swap) in route.hops.iter().zip(route.swaps.iter()) {
+        instructions.push(build_swap_instruction(&hop.market.accounts, user, &swap.parameters));
+    }
+
+    if let Some((wsol_account, token_program)) = wsol_account {
+        instructions.push(close_account(&token_program, &wsol_account, user, user, &[user]).unwrap());
+    }
+
+    instructions
+}
+
+pub fn build_route_transaction(route: &RouteQuote, user: &Pubkey, options: &SwapOptions) -> Transaction {
+    let instructions = build_route_instructions(route, user, options);
+
+    Transaction::new_unsigned(Message::new(&instructions, Some(user)))
+}
@@ -0,0 +1,373 @@
+use anyhow::{anyhow, Context, Result};
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::{json, Value};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+use crate::authority::{check_swap_access, cosign_swap_transaction, validate_cosign_request, SwapAllowlist};
+use crate::market::Market;
+use crate::price::{is_phase_a, sqrt_price_x96_to_price};
+use crate::quote::Quote;
+use crate::store::MarketStore;
+use crate::supply::supply_progress;
+use crate::swap::{build_swap_transaction, quote_swap_with_legs, SwapAccounts, SwapOptions, SwapParameters};
+
+pub struct AppState {
+    pub store: MarketStore,
+    // When set, built transactions use it as fee payer and come back partially signed
+    pub fee_payer: Option<Keypair>,
+    // When set, swaps on the markets it restricts come back co-signed, and `/swap/cosign` signs the ones built elsewhere
+    pub swap_authority: Option<Keypair>,
+    // Swaps the swap authority co-signs, on both endpoints
+    pub swap_allowlist: SwapAllowlist,
+}
+
+#[derive(Debug)]
+pub struct ApiError(StatusCode, anyhow::Error);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1.to_string() }))).into_response()
+    }
+}
+
+fn bad_request(err: anyhow::Error) -> ApiError {
+    ApiError(StatusCode::BAD_REQUEST, err)
+}
+
+fn bad_gateway(err: anyhow::Error) -> ApiError {
+    ApiError(StatusCode::BAD_GATEWAY, err)
+}
+
+fn forbidden(err: anyhow::Error) -> ApiError {
+    ApiError(StatusCode::FORBIDDEN, err)
+}
+
+pub async fn serve(
+    bind: &str,
+    rpc_url: String,
+    cache_ttl: Duration,
+    fee_payer: Option<Keypair>,
+    swap_authority: Option<Keypair>,
+    swap_allowlist: SwapAllowlist,
+) -> Result<()> {
+    let state = Arc::new(AppState {
+        store: MarketStore::new(rpc_url, CommitmentConfig::processed(), cache_ttl),
+        fee_payer,
+        swap_authority,
+        swap_allowlist,
+    });
+
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .with_context(|| format!("Failed to bind {}", bind))?;
+    println!("Listening on {}", bind);
+
+    axum::serve(listener, router(state)).await?;
+
+    Ok(())
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/markets/{address}", get(get_market))
+        .route("/markets/{address}/quote", get(get_quote))
+        .route("/swap/build", post(build_swap))
+        .route("/swap/cosign", post(cosign_swap))
+        .with_state(state)
+}
+
+async fn get_market(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let address = parse_pubkey(&address).map_err(bad_request)?;
+    let market = state.store.get(&address).await.map_err(bad_gateway)?;
+
+    Ok(Json(market_to_json(&address, &market)))
+}
+
+// GET /markets/{address}/quote?side=buy|sell&mode=exact_in|exact_out&amount=&slippage_bps=
+async fn get_quote(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Json<Value>, ApiError> {
+    let address = parse_pubkey(&address).map_err(bad_request)?;
+    let parameters = parse_swap_parameters(&query).map_err(bad_request)?;
+    let slippage_bps = parse_optional_u64(&query, "slippage_bps")
+        .map_err(bad_request)?
+        .unwrap_or(0);
+
+    let market = state.store.get(&address).await.map_err(bad_gateway)?;
+    let quote = quote_swap_with_legs(&market, &parameters).map_err(|err| bad_request(err.into()))?;
+    let parameters = parameters
+        .with_slippage(&quote, slippage_bps)
+        .map_err(bad_request)?;
+
+    Ok(Json(json!({
+        "market": address.to_string(),
+        "parameters": swap_parameters_to_json(&parameters),
+        "slippage_bps": slippage_bps,
+        "quote": quote_to_json(&quote),
+    })))
+}
+
+// POST /swap/build
+// { "user", "market", "side", "mode", "amount", "slippage_bps", "priority_fee_micro_lamports", "compute_unit_limit" }
+async fn build_swap(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    let user = parse_pubkey(body_str(&body, "user").map_err(bad_request)?).map_err(bad_request)?;
+    let address = parse_pubkey(body_str(&body, "market").map_err(bad_request)?).map_err(bad_request)?;
+    let parameters = SwapParameters::parse(
+        body_str(&body, "side").map_err(bad_request)?,
+        body.get("mode").and_then(Value::as_str).unwrap_or("exact_in"),
+        body_u64(&body, "amount").map_err(bad_request)?.ok_or(bad_request(anyhow!("Missing amount")))?,
+        0,
+    )
+    .map_err(bad_request)?;
+    let slippage_bps = body_u64(&body, "slippage_bps").map_err(bad_request)?.unwrap_or(0);
+    let options = SwapOptions {
+        compute_unit_limit: body_u64(&body, "compute_unit_limit")
+            .map_err(bad_request)?
+            .map(|limit| u32::try_from(limit).map_err(|_| anyhow!("Invalid compute_unit_limit: {}", limit)))
+            .transpose()
+            .map_err(bad_request)?,
+        priority_fee_micro_lamports: body_u64(&body, "priority_fee_micro_lamports").map_err(bad_request)?,
+    };
+
+    let market = state.store.get(&address).await.map_err(bad_gateway)?;
+    let fee_payer = state.fee_payer.as_ref().map(|keypair| keypair.pubkey()).unwrap_or(user);
+    let cosigner = swap_cosigner(&state, &user, &address, &market)?;
+
+    let quote = quote_swap_with_legs(&market, &parameters).map_err(|err| bad_request(err.into()))?;
+    let parameters = parameters
+        .with_slippage(&quote, slippage_bps)
+        .map_err(bad_request)?;
+
+    let token_program0 = state.store.token_program(&market.token_mint0).await.map_err(bad_gateway)?;
+    let token_program1 = state.store.token_program(&market.token_mint1).await.map_err(bad_gateway)?;
+    let accounts = SwapAccounts::new(&address, &market, &token_program0, &token_program1);
+
+    let (recent_blockhash, last_valid_block_height) = state
+        .store
+        .rpc_client()
+        .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+        .await
+        .map_err(|err| bad_gateway(err.into()))?;
+
+    let mut transaction = build_swap_transaction(&accounts, &user, &fee_payer, &parameters, &options);
+    transaction.message.recent_blockhash = recent_blockhash;
+
+    if let Some(keypair) = state.fee_payer.as_ref() {
+        transaction
+            .try_partial_sign(&[keypair], recent_blockhash)
+            .map_err(|err| ApiError(StatusCode::INTERNAL_SERVER_ERROR, err.into()))?;
+    }
+    if let Some(keypair) = cosigner {
+        transaction
+            .try_partial_sign(&[keypair], recent_blockhash)
+            .map_err(|err| ApiError(StatusCode::INTERNAL_SERVER_ERROR, err.into()))?;
+    }
+
+    let serialized_transaction = bincode::serialize(&transaction)
+        .map_err(|err| ApiError(StatusCode::INTERNAL_SERVER_ERROR, err.into()))?;
+
+    Ok(Json(json!({
+        "transaction": general_purpose::STANDARD.encode(&serialized_transaction),
+        "fee_payer": fee_payer.to_string(),
+        "swap_authority": market.swap_authority.map(|key| key.to_string()),
+        "recent_blockhash": recent_blockhash.to_string(),
+        "last_valid_block_height": last_valid_block_height,
+        "market": address.to_string(),
+        "parameters": swap_parameters_to_json(&parameters),
+        "slippage_bps": slippage_bps,
+        "quote": quote_to_json(&quote),
+    })))
+}
+
+// POST /swap/cosign
+// { "transaction" }, a base64 transaction with swaps on markets restricted by the service swap authority
+// Only signed when the allowlist approves every swap of the transaction
+async fn cosign_swap(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    let keypair = state
+        .swap_authority
+        .as_ref()
+        .ok_or(ApiError(StatusCode::NOT_FOUND, anyhow!("No swap authority configured")))?;
+    let serialized_transaction = general_purpose::STANDARD
+        .decode(body_str(&body, "transaction").map_err(bad_request)?)
+        .map_err(|_| bad_request(anyhow!("Invalid base64 transaction")))?;
+    let mut transaction: Transaction =
+        bincode::deserialize(&serialized_transaction).map_err(|_| bad_request(anyhow!("Invalid transaction")))?;
+
+    for (user, market_address) in validate_cosign_request(&transaction, &keypair.pubkey()).map_err(forbidden)? {
+        state.swap_allowlist.check(&user, &market_address).map_err(forbidden)?;
+    }
+    cosign_swap_transaction(&mut transaction, keypair).map_err(forbidden)?;
+
+    let serialized_transaction = bincode::serialize(&transaction)
+        .map_err(|err| ApiError(StatusCode::INTERNAL_SERVER_ERROR, err.into()))?;
+
+    Ok(Json(json!({
+        "transaction": general_purpose::STANDARD.encode(&serialized_transaction),
+        "swap_authority": keypair.pubkey().to_string(),
+    })))
+}
+
+// Key co-signing the swap of `user`, when the market is restricted by the service swap authority
+// Access only depends on the caller: a user who isn't the authority of a restricted market needs the service to
+// co-sign, which it only does for the swaps its allowlist approves
+pub fn swap_cosigner<'a>(
+    state: &'a AppState,
+    user: &Pubkey,
+    market_address: &Pubkey,
+    market: &Market,
+) -> Result<Option<&'a Keypair>, ApiError> {
+    match state.swap_authority.as_ref() {
+        Some(keypair) if market.swap_authority == Some(keypair.pubkey()) && *user != keypair.pubkey() => {
+            state.swap_allowlist.check(user, market_address).map_err(forbidden)?;
+            Ok(Some(keypair))
+        }
+        _ => check_swap_access(market_address, market, &[*user]).map(|_| None).map_err(forbidden),
+    }
+}
+
+fn body_str<'a>(body: &'a Value, key: &str) -> Result<&'a str> {
+    body.get(key)
+        .and_then(Value::as_str)
+        .ok_or(anyhow!("Missing {}", key))
+}
+
+// Accepts both JSON numbers and strings, as JS clients can't represent every u64 as a number
+fn body_u64(body: &Value, key: &str) -> Result<Option<u64>> {
+    match body.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(number)) => number
+            .as_u64()
+            .map(Some)
+            .ok_or(anyhow!("Invalid {}: {}", key, number)),
+        Some(Value::String(value)) => value
+            .parse::<u64>()
+            .map(Some)
+            .map_err(|_| anyhow!("Invalid {}: {}", key, value)),
+        Some(value) => Err(anyhow!("Invalid {}: {}", key, value)),
+    }
+}
+
+pub fn parse_pubkey(address: &str) -> Result<Pubkey> {
+    Pubkey::from_str(address).map_err(|_| anyhow!("Invalid address: {}", address))
+}
+
+fn parse_swap_parameters(query: &HashMap<String, String>) -> Result<SwapParameters> {
+    let side = query.get("side").ok_or(anyhow!("Missing side"))?;
+    let mode = query
+        .get("mode")
+        .map(String::as_str)
+        .unwrap_or("exact_in");
+    let amount = parse_optional_u64(query, "amount")?.ok_or(anyhow!("Missing amount"))?;
+
+    SwapParameters::parse(side, mode, amount, 0)
+}
+
+fn parse_optional_u64(query: &HashMap<String, String>, key: &str) -> Result<Option<u64>> {
+    query
+        .get(key)
+        .map(|value| {
+            value
+                .parse::<u64>()
+                .map_err(|_| anyhow!("Invalid {}: {}", key, value))
+        })
+        .transpose()
+}
+
+// u128 values are serialized as strings, as they don't fit in a JSON number
+pub fn market_to_json(address: &Pubkey, market: &Market) -> Value {
+    let supply = supply_progress(market);
+
+    json!({
+        "address": address.to_string(),
+        "config": market.config.to_string(),
+        "creator": market.creator.to_string(),
+        "swap_authority": market.swap_authority.map(|key| key.to_string()),
+        "token_mint0": market.token_mint0.to_string(),
+        "token_mint1": market.token_mint1.to_string(),
+        "reserve0": market.reserve0.to_string(),
+        "reserve1": market.reserve1.to_string(),
+        "fee_reserve": market.fee_reserve.map(|key| key.to_string()),
+        "fee_reserve_last_update": market.fee_reserve_last_update,
+        "settings": {
+            "max_supply": market.settings.max_supply,
+            "sqrt_price_a_x96": market.settings.sqrt_price_a_x96.to_string(),
+            "sqrt_price_b_x96": market.settings.sqrt_price_b_x96.to_string(),
+            "liquidity_a": market.settings.liquidity_a.to_string(),
+            "liquidity_b": market.settings.liquidity_b.to_string(),
+            "fee": market.settings.fee,
+        },
+        "sqrt_price_x96": market.sqrt_price_x96.to_string(),
+        "prices": {
+            "price": sqrt_price_x96_to_price(market.sqrt_price_x96),
+            "price_a": sqrt_price_x96_to_price(market.settings.sqrt_price_a_x96),
+            "price_b": sqrt_price_x96_to_price(market.settings.sqrt_price_b_x96),
+            "phase": if is_phase_a(market, market.sqrt_price_x96) { "A" } else { "B" },
+        },
+        "supply": supply.as_ref().ok().map(|supply| json!({
+            "sold": supply.sold,
+            "remaining": supply.remaining,
+            "progress": supply.progress,
+            "raised": supply.raised,
+            "total_raise": supply.total_raise,
+            "end_sqrt_price_x96": supply.end_sqrt_price_x96.map(|sqrt_price| sqrt_price.to_string()),
+        })),
+        // Why `supply` is null, the rest of the market being valid anyway
+        "supply_error": supply.as_ref().err().map(|err| err.to_string()),
+    })
+}
+
+pub fn quote_to_json(quote: &Quote) -> Value {
+    json!({
+        "amount_in": quote.amount_in,
+        "amount_out": quote.amount_out,
+        "fee_amount_token_in": quote.fee_amount_token_in,
+        "fee_amount_token_1": quote.fee_amount_token_1,
+        "next_sqrt_price": quote.next_sqrt_price.to_string(),
+        "next_price": sqrt_price_x96_to_price(quote.next_sqrt_price),
+        "legs": quote.legs.iter().map(|leg| json!({
+            "phase": format!("{:?}", leg.phase),
+            "sqrt_price_start": leg.sqrt_price_start.to_string(),
+            "sqrt_price_end": leg.sqrt_price_end.to_string(),
+            "liquidity": leg.liquidity.to_string(),
+            "amount_in": leg.amount_in,
+            "amount_out": leg.amount_out,
+            "fee_amount": leg.fee_amount,
+        })).collect::<Vec<_>>(),
+        "crossed_phase_boundary": quote.crossed_phase_boundary,
+        "hit_sqrt_price_limit": quote.hit_sqrt_price_limit,
+    })
+}
+
+pub fn swap_parameters_to_json(parameters: &SwapParameters) -> Value {
+    json!({
+        "side": parameters.side(),
+        "mode": parameters.mode(),
+        "amount": parameters.amount(),
+        "threshold": parameters.threshold(),
+    })
+}
@@ -0,0 +1,103 @@
+use anyhow::{anyhow, Result};
+use ruint::aliases::{U256, U512};
+
+use crate::market::Market;
+use crate::quote::{PreparedMarket, Quote};
+use crate::swap::{default_sqrt_price_limit, quote_swap, QuotedSwap, SwapParameters, MAX_BPS};
+use crate::swap_math::SQRT_PRICE_SHIFT;
+
+// Largest amount swappable in a single instruction, as `quote::quote` takes a signed delta
+pub const MAX_SWAP_AMOUNT: u64 = i64::MAX as u64;
+
+// Bisects on the largest amount up to `MAX_SWAP_AMOUNT` satisfying `is_valid`,
+// which must hold for 0 and stop holding past some amount
+pub fn largest_amount(is_valid: impl Fn(u64) -> bool) -> u64 {
+    if is_valid(MAX_SWAP_AMOUNT) {
+        return MAX_SWAP_AMOUNT;
+    }
+
+    let (mut low, mut high) = (0, MAX_SWAP_AMOUNT);
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        if is_valid(middle) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    low
+}
+
+// The price impact is the move of the market price, not the average price paid,
+// e.g. 100 bps allows the price to end up at most 1% above the current one
+pub fn max_buy_within_price_impact(market: &Market, max_price_impact_bps: u64) -> Result<QuotedSwap> {
+    // next_sqrt_price^2 * MAX_BPS <= sqrt_price^2 * (MAX_BPS + max_price_impact_bps)
+    let sqrt_price = U512::from(market.sqrt_price_x96);
+    let max_next_price = sqrt_price * sqrt_price * U512::from(MAX_BPS.saturating_add(max_price_impact_bps));
+
+    solve(market, SwapParameters::BuyExactIn(0, 0), |_, quote| {
+        let next_sqrt_price = U512::from(quote.next_sqrt_price);
+        next_sqrt_price * next_sqrt_price * U512::from(MAX_BPS) <= max_next_price
+    })
+}
+
+// Buys as many tokens as possible for at most `budget` of token 1, fees included
+// An exact out buy usually gets a few more tokens than an exact in buy of the whole budget, as the latter rounds
+// the amount available after fees down, but far in the tail of the curve rounding can favor the exact in buy instead
+// The threshold of an exact out buy is set to the budget, so it can't spend more even if the price moves
+pub fn max_buy_within_budget(market: &Market, budget: u64) -> Result<QuotedSwap> {
+    let exact_out = solve(market, SwapParameters::BuyExactOut(0, budget), |amount, quote| {
+        quote.amount_out == amount && quote.amount_in <= budget
+    })?;
+
+    match quote_swap(market, &SwapParameters::BuyExactIn(budget, 0)) {
+        Ok(quote) if budget <= MAX_SWAP_AMOUNT && quote.amount_out > exact_out.quote.amount_out => Ok(QuotedSwap {
+            parameters: SwapParameters::BuyExactIn(budget, 0),
+            quote,
+        }),
+        _ => Ok(exact_out),
+    }
+}
+
+// `min_average_price_x96` is the raw price of token 0 in token 1 as a Q64.96, i.e. amount_out * 2^96 / amount_in,
+// with `amount_in` including the fee
+// Sells too large to be filled before reaching the start of the curve are never returned
+pub fn max_sell_above_average_price(market: &Market, min_average_price_x96: u128) -> Result<QuotedSwap> {
+    solve(market, SwapParameters::SellExactIn(0, 0), |amount, quote| {
+        quote.amount_in == amount
+            && U256::from(quote.amount_out) << SQRT_PRICE_SHIFT
+                >= U256::from(quote.amount_in) * U256::from(min_average_price_x96)
+    })
+}
+
+// Largest amount of `parameters` whose quote satisfies `is_valid`, checked once more on the final quote
+// so that the result never relies on the constraint being perfectly monotonic
+fn solve(
+    market: &Market,
+    parameters: SwapParameters,
+    is_valid: impl Fn(u64, &Quote) -> bool,
+) -> Result<QuotedSwap> {
+    let prepared = PreparedMarket::new(&market.settings);
+    let zero_for_one = parameters.zero_for_one();
+    let sqrt_price_limit = default_sqrt_price_limit(market, zero_for_one);
+    let quote_amount = |amount: u64| {
+        prepared.quote(
+            market.sqrt_price_x96,
+            zero_for_one,
+            parameters.with_amount(amount).delta_amount()?,
+            sqrt_price_limit,
+        )
+    };
+
+    let amount = largest_amount(|amount| quote_amount(amount).is_ok_and(|quote| is_valid(amount, &quote)));
+    let quote = quote_amount(amount)?;
+    if !is_valid(amount, &quote) {
+        return Err(anyhow!("NoValidAmount: {:?}", parameters));
+    }
+
+    Ok(QuotedSwap {
+        parameters: parameters.with_amount(amount),
+        quote,
+    })
+}
@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Result};
+
+use crate::error::SwapMathError;
+use crate::market::Market;
+use crate::quote::{PreparedMarket, Quote};
+use crate::supply::{quote_reserve, tokens_sold};
+use crate::swap::{default_sqrt_price_limit, SwapParameters};
+
+// A market moved by simulated swaps, to play sequences of them without sending anything
+// Cloning it branches off a what-if scenario
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarketState {
+    market: Market,
+    prepared: PreparedMarket,
+    // Balances of the market reserves, fees included as if none were withdrawn
+    pub reserve0: u64,
+    pub reserve1: u64,
+    // Fees of the applied swaps, in token 1 as the program converts the fees of sells
+    pub fees_token_1: u64,
+    pub swaps: u64,
+}
+
+// Outcome of a swap applied to a `MarketState`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fill {
+    pub parameters: SwapParameters,
+    pub quote: Quote,
+    pub sqrt_price_before: u128,
+    // Includes the fee conversion of sells, so it's the price the next swap starts from
+    pub sqrt_price_after: u128,
+}
+
+impl MarketState {
+    // Reserves are derived from the curve, holding what it owes to sellers and no fees
+    pub fn new(market: Market) -> Result<Self> {
+        let settings = &market.settings;
+        let reserve0 = settings.max_supply.saturating_sub(tokens_sold(settings, market.sqrt_price_x96)?);
+        let reserve1 = quote_reserve(settings, market.sqrt_price_x96)?;
+
+        Ok(Self::with_reserves(market, reserve0, reserve1))
+    }
+
+    // Starts from the actual balances of the reserves, e.g. fetched along with the market
+    pub fn with_reserves(market: Market, reserve0: u64, reserve1: u64) -> Self {
+        Self {
+            prepared: PreparedMarket::new(&market.settings),
+            market,
+            reserve0,
+            reserve1,
+            fees_token_1: 0,
+            swaps: 0,
+        }
+    }
+
+    pub fn market(&self) -> &Market {
+        &self.market
+    }
+
+    pub fn sqrt_price_x96(&self) -> u128 {
+        self.market.sqrt_price_x96
+    }
+
+    // Same as `swap::quote_swap` on the current state
+    pub fn quote(&self, parameters: &SwapParameters) -> Result<Quote, SwapMathError> {
+        let zero_for_one = parameters.zero_for_one();
+
+        self.prepared.quote(
+            self.market.sqrt_price_x96,
+            zero_for_one,
+            parameters.delta_amount()?,
+            default_sqrt_price_limit(&self.market, zero_for_one),
+        )
+    }
+
+    // Runs the swap as the program would, thresholds included, and leaves the state untouched when it fails
+    // Exact out swaps need their threshold set, e.g. with `SwapParameters::with_slippage`
+    pub fn apply(&mut self, parameters: &SwapParameters) -> Result<Fill> {
+        let quote = self.quote(parameters)?;
+
+        let meets_threshold = if parameters.is_exact_in() {
+            quote.amount_out >= parameters.threshold()
+        } else {
+            quote.amount_in <= parameters.threshold()
+        };
+        if !meets_threshold {
+            return Err(anyhow!(
+                "AmountThresholdNotMet: {} in for {} out, threshold {}",
+                quote.amount_in,
+                quote.amount_out,
+                parameters.threshold()
+            ));
+        }
+
+        // The fee of a sell stays in reserve 0 and its token 1 value in reserve 1
+        let (reserve0, reserve1) = if parameters.zero_for_one() {
+            (self.reserve0.checked_add(quote.amount_in), self.reserve1.checked_sub(quote.amount_out))
+        } else {
+            (self.reserve0.checked_sub(quote.amount_out), self.reserve1.checked_add(quote.amount_in))
+        };
+        let reserve0 = reserve0.ok_or(anyhow!("InsufficientReserve0: {} for {}", self.reserve0, quote.amount_out))?;
+        let reserve1 = reserve1.ok_or(anyhow!("InsufficientReserve1: {} for {}", self.reserve1, quote.amount_out))?;
+        let fees_token_1 = self
+            .fees_token_1
+            .checked_add(quote.fee_amount_token_1)
+            .ok_or(SwapMathError::FeeAmountOverflow)?;
+
+        let sqrt_price_before = self.market.sqrt_price_x96;
+        self.market.sqrt_price_x96 = quote.next_sqrt_price;
+        self.reserve0 = reserve0;
+        self.reserve1 = reserve1;
+        self.fees_token_1 = fees_token_1;
+        self.swaps += 1;
+
+        Ok(Fill {
+            parameters: parameters.clone(),
+            sqrt_price_after: quote.next_sqrt_price,
+            sqrt_price_before,
+            quote,
+        })
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+
+use crate::market::Market;
+
+struct CachedMarket {
+    market: Market,
+    fetched_at: Instant,
+}
+
+// Keeps the last decoded state of every market requested, refetching it once older than `ttl`
+// Mint owners never change, so token programs are cached forever
+pub struct MarketStore {
+    rpc_client: RpcClient,
+    ttl: Duration,
+    markets: RwLock<HashMap<Pubkey, CachedMarket>>,
+    token_programs: RwLock<HashMap<Pubkey, Pubkey>>,
+}
+
+impl MarketStore {
+    pub fn new(rpc_url: String, commitment: CommitmentConfig, ttl: Duration) -> Self {
+        Self {
+            rpc_client: RpcClient::new_with_commitment(rpc_url, commitment),
+            ttl,
+            markets: RwLock::new(HashMap::new()),
+            token_programs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn rpc_client(&self) -> &RpcClient {
+        &self.rpc_client
+    }
+
+    pub async fn get(&self, address: &Pubkey) -> Result<Market> {
+        if let Some(cached) = self.markets.read().unwrap().get(address) {
+            if cached.fetched_at.elapsed() < self.ttl {
+                return Ok(cached.market.clone());
+            }
+        }
+
+        self.refresh(address).await
+    }
+
+    pub async fn refresh(&self, address: &Pubkey) -> Result<Market> {
+        let account = self
+            .rpc_client
+            .get_account(address)
+            .await
+            .with_context(|| format!("Failed to fetch market {}", address))?;
+        let market = Market::from_bytes(&account.data)
+            .with_context(|| format!("Failed to decode market {}", address))?;
+
+        self.markets.write().unwrap().insert(
+            *address,
+            CachedMarket {
+                market: market.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+
+        Ok(market)
+    }
+
+    pub async fn token_program(&self, mint: &Pubkey) -> Result<Pubkey> {
+        if let Some(token_program) = self.token_programs.read().unwrap().get(mint) {
+            return Ok(*token_program);
+        }
+
+        let account = self
+            .rpc_client
+            .get_account(mint)
+            .await
+            .with_context(|| format!("Failed to fetch mint {}", mint))?;
+
+        self.token_programs.write().unwrap().insert(*mint, account.owner);
+
+        Ok(account.owner)
+    }
+}
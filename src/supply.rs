@@ -0,0 +1,109 @@
+use crate::error::SwapMathError;
+use crate::market::{Market, MarketSettings};
+use crate::swap_math::{get_amount_0, get_amount_1, get_next_sqrt_ratio_from_amount_0};
+
+type Result<T> = std::result::Result<T, SwapMathError>;
+
+// Where a market stands on its curve, amounts exclude the fees
+#[derive(Clone, Debug, PartialEq)]
+pub struct SupplyProgress {
+    pub max_supply: u64,
+    pub sold: u64,
+    pub remaining: u64,
+    // Share of `max_supply` sold, in percent
+    pub progress: f64,
+    pub raised: u64,
+    // None when the curve can't sell the whole supply, see `curve_end_sqrt_price`
+    pub total_raise: Option<u64>,
+    pub end_sqrt_price_x96: Option<u128>,
+}
+
+// Token 0 bought from the start of the curve up to `sqrt_price_x96`, phase by phase
+// Amounts are rounded down, so they may be slightly below the sum of the swaps that got there
+pub fn tokens_sold(settings: &MarketSettings, sqrt_price_x96: u128) -> Result<u64> {
+    let sold = amounts_to(settings, sqrt_price_x96, get_amount_0, false)?;
+
+    sold.try_into().map_err(|_| SwapMathError::AmountOverflow)
+}
+
+// Token 1 paid for the tokens sold, also rounded down
+pub fn quote_raised(settings: &MarketSettings, sqrt_price_x96: u128) -> Result<u64> {
+    let raised = amounts_to(settings, sqrt_price_x96, get_amount_1, false)?;
+
+    raised.try_into().map_err(|_| SwapMathError::AmountOverflow)
+}
+
+// Token 1 the curve owes to sellers to be bought back down to its start, rounded up so it's never underestimated
+pub fn quote_reserve(settings: &MarketSettings, sqrt_price_x96: u128) -> Result<u64> {
+    let reserve = amounts_to(settings, sqrt_price_x96, get_amount_1, true)?;
+
+    reserve.try_into().map_err(|_| SwapMathError::AmountOverflow)
+}
+
+pub fn tokens_remaining(settings: &MarketSettings, sqrt_price_x96: u128) -> Result<u64> {
+    Ok(settings.max_supply.saturating_sub(tokens_sold(settings, sqrt_price_x96)?))
+}
+
+pub fn progress(settings: &MarketSettings, sqrt_price_x96: u128) -> Result<f64> {
+    if settings.max_supply == 0 {
+        return Ok(100.0);
+    }
+
+    Ok(tokens_sold(settings, sqrt_price_x96)? as f64 * 100.0 / settings.max_supply as f64)
+}
+
+// Price at which the whole `max_supply` is sold
+// Fails when the liquidity of the last phase can't hold the rest of the supply, its price would be infinite
+pub fn curve_end_sqrt_price(settings: &MarketSettings) -> Result<u128> {
+    let supply_a = get_amount_0(settings.sqrt_price_a_x96, settings.sqrt_price_b_x96, settings.liquidity_a, false)?;
+
+    let (sqrt_price, liquidity, remaining) = match u128::from(settings.max_supply).checked_sub(supply_a) {
+        Some(remaining) => (settings.sqrt_price_b_x96, settings.liquidity_b, remaining),
+        None => (settings.sqrt_price_a_x96, settings.liquidity_a, u128::from(settings.max_supply)),
+    };
+    let remaining = i64::try_from(remaining).map_err(|_| SwapMathError::AmountOverflow)?;
+
+    // Removing token 0 from the pool moves the price up
+    get_next_sqrt_ratio_from_amount_0(sqrt_price, liquidity, -remaining)
+}
+
+// Token 1 raised once the curve is bought out
+pub fn total_raise(settings: &MarketSettings) -> Result<u64> {
+    quote_raised(settings, curve_end_sqrt_price(settings)?)
+}
+
+pub fn supply_progress(market: &Market) -> Result<SupplyProgress> {
+    let settings = &market.settings;
+    let sold = tokens_sold(settings, market.sqrt_price_x96)?;
+    let end_sqrt_price_x96 = curve_end_sqrt_price(settings).ok();
+
+    Ok(SupplyProgress {
+        max_supply: settings.max_supply,
+        sold,
+        remaining: settings.max_supply.saturating_sub(sold),
+        progress: progress(settings, market.sqrt_price_x96)?,
+        raised: quote_raised(settings, market.sqrt_price_x96)?,
+        total_raise: end_sqrt_price_x96.and_then(|end_sqrt_price_x96| quote_raised(settings, end_sqrt_price_x96).ok()),
+        end_sqrt_price_x96,
+    })
+}
+
+// Sum of `get_amount` over the part of each phase below `sqrt_price_x96`
+fn amounts_to(
+    settings: &MarketSettings,
+    sqrt_price_x96: u128,
+    get_amount: fn(u128, u128, u128, bool) -> Result<u128>,
+    round_up: bool,
+) -> Result<u128> {
+    let sqrt_price_x96 = sqrt_price_x96.max(settings.sqrt_price_a_x96);
+    let phase_a_end = sqrt_price_x96.min(settings.sqrt_price_b_x96);
+
+    let mut amount = get_amount(settings.sqrt_price_a_x96, phase_a_end, settings.liquidity_a, round_up)?;
+    if sqrt_price_x96 > settings.sqrt_price_b_x96 {
+        amount = amount
+            .checked_add(get_amount(settings.sqrt_price_b_x96, sqrt_price_x96, settings.liquidity_b, round_up)?)
+            .ok_or(SwapMathError::AmountOverflow)?;
+    }
+
+    Ok(amount)
+}
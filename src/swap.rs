@@ -0,0 +1,330 @@
+use anyhow::{Result, anyhow};
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    pubkey::Pubkey,
+    system_instruction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token::instruction::{close_account, sync_native};
+
+use crate::error::SwapMathError;
+use crate::market::{Market, TOKENMILL_PROGRAM};
+use crate::quote::{quote, quote_with_legs, Quote};
+use crate::token::{get_associated_token_address, is_native_mint};
+
+pub const MAX_BPS: u64 = 10_000;
+
+pub const SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+pub const EVENT_AUTHORITY_SEED: &[u8] = b"__event_authority";
+pub const TOKENMILL_PROTOCOL_FEE_RESERVE: &str = "H5ykxF3zEN6biiXPLM8u4bbNMXxiAbBMQx5MXth1sC6";
+
+// First value is the amount to swap, second one the slippage threshold:
+// minimum amount out for exact in swaps, maximum amount in for exact out swaps
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SwapParameters {
+    BuyExactIn(u64, u64),
+    BuyExactOut(u64, u64),
+    SellExactIn(u64, u64),
+    SellExactOut(u64, u64),
+}
+
+impl SwapParameters {
+    // Builds the parameters from the `side` and `mode` strings used by the CLI and the HTTP service
+    pub fn parse(side: &str, mode: &str, amount: u64, threshold: u64) -> Result<Self> {
+        match (side, mode) {
+            ("buy", "exact_in") => Ok(Self::BuyExactIn(amount, threshold)),
+            ("buy", "exact_out") => Ok(Self::BuyExactOut(amount, threshold)),
+            ("sell", "exact_in") => Ok(Self::SellExactIn(amount, threshold)),
+            ("sell", "exact_out") => Ok(Self::SellExactOut(amount, threshold)),
+            _ => Err(anyhow!("InvalidSwapParameters: side={} mode={}", side, mode)),
+        }
+    }
+
+    pub fn side(&self) -> &'static str {
+        if self.zero_for_one() {
+            "sell"
+        } else {
+            "buy"
+        }
+    }
+
+    pub fn mode(&self) -> &'static str {
+        if self.is_exact_in() {
+            "exact_in"
+        } else {
+            "exact_out"
+        }
+    }
+
+    // zero_for_one : direction of swap
+    // false : token 1 -> token 0 (buy)
+    // true : token 0 -> token 1 (sell)
+    pub fn zero_for_one(&self) -> bool {
+        matches!(self, Self::SellExactIn(..) | Self::SellExactOut(..))
+    }
+
+    pub fn is_exact_in(&self) -> bool {
+        matches!(self, Self::BuyExactIn(..) | Self::SellExactIn(..))
+    }
+
+    pub fn amount(&self) -> u64 {
+        match self {
+            Self::BuyExactIn(amount, _)
+            | Self::BuyExactOut(amount, _)
+            | Self::SellExactIn(amount, _)
+            | Self::SellExactOut(amount, _) => *amount,
+        }
+    }
+
+    pub fn threshold(&self) -> u64 {
+        match self {
+            Self::BuyExactIn(_, threshold)
+            | Self::BuyExactOut(_, threshold)
+            | Self::SellExactIn(_, threshold)
+            | Self::SellExactOut(_, threshold) => *threshold,
+        }
+    }
+
+    // Positive for exact in swaps, negative for exact out swaps, as expected by `quote::quote`
+    pub fn delta_amount(&self) -> Result<i64, SwapMathError> {
+        let amount = i64::try_from(self.amount()).map_err(|_| SwapMathError::DeltaAmountOverflow)?;
+
+        if self.is_exact_in() {
+            Ok(amount)
+        } else {
+            Ok(-amount)
+        }
+    }
+
+    pub fn with_amount(&self, amount: u64) -> Self {
+        match self {
+            Self::BuyExactIn(_, threshold) => Self::BuyExactIn(amount, *threshold),
+            Self::BuyExactOut(_, threshold) => Self::BuyExactOut(amount, *threshold),
+            Self::SellExactIn(_, threshold) => Self::SellExactIn(amount, *threshold),
+            Self::SellExactOut(_, threshold) => Self::SellExactOut(amount, *threshold),
+        }
+    }
+
+    pub fn with_threshold(&self, threshold: u64) -> Self {
+        match self {
+            Self::BuyExactIn(amount, _) => Self::BuyExactIn(*amount, threshold),
+            Self::BuyExactOut(amount, _) => Self::BuyExactOut(*amount, threshold),
+            Self::SellExactIn(amount, _) => Self::SellExactIn(*amount, threshold),
+            Self::SellExactOut(amount, _) => Self::SellExactOut(*amount, threshold),
+        }
+    }
+
+    // Returns a copy of the parameters with the threshold derived from the quote and the slippage
+    pub fn with_slippage(&self, quote: &Quote, slippage_bps: u64) -> Result<Self> {
+        if slippage_bps > MAX_BPS {
+            return Err(anyhow!("InvalidSlippage: {}", slippage_bps));
+        }
+
+        let threshold = if self.is_exact_in() {
+            u128::from(quote.amount_out) * u128::from(MAX_BPS - slippage_bps) / u128::from(MAX_BPS)
+        } else {
+            (u128::from(quote.amount_in) * u128::from(MAX_BPS + slippage_bps))
+                .div_ceil(u128::from(MAX_BPS))
+        };
+
+        Ok(self.with_threshold(
+            u64::try_from(threshold).map_err(|_| anyhow!("AmountOverflow"))?,
+        ))
+    }
+}
+
+// Swap parameters along with the quote they were sized from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuotedSwap {
+    pub parameters: SwapParameters,
+    pub quote: Quote,
+}
+
+// Sells can't go below the start of the curve, buys are only limited by the liquidity
+pub fn default_sqrt_price_limit(market: &Market, zero_for_one: bool) -> u128 {
+    if zero_for_one {
+        market.settings.sqrt_price_a_x96
+    } else {
+        u128::MAX
+    }
+}
+
+pub fn quote_swap(market: &Market, parameters: &SwapParameters) -> Result<Quote, SwapMathError> {
+    let zero_for_one = parameters.zero_for_one();
+
+    quote(
+        market,
+        zero_for_one,
+        parameters.delta_amount()?,
+        default_sqrt_price_limit(market, zero_for_one),
+    )
+}
+
+// Same as `quote_swap`, with the legs of the swap filled in
+pub fn quote_swap_with_legs(market: &Market, parameters: &SwapParameters) -> Result<Quote, SwapMathError> {
+    let zero_for_one = parameters.zero_for_one();
+
+    quote_with_legs(
+        market,
+        zero_for_one,
+        parameters.delta_amount()?,
+        default_sqrt_price_limit(market, zero_for_one),
+    )
+}
+
+pub fn find_event_authority() -> Pubkey {
+    Pubkey::find_program_address(&[EVENT_AUTHORITY_SEED], &Pubkey::from_str_const(TOKENMILL_PROGRAM)).0
+}
+
+// Accounts of a market needed to swap on it, token programs are resolved from the mint owners
+#[derive(Clone, Debug)]
+pub struct SwapAccounts {
+    pub market: Pubkey,
+    pub config: Pubkey,
+    pub token_mint0: Pubkey,
+    pub token_mint1: Pubkey,
+    pub reserve0: Pubkey,
+    pub reserve1: Pubkey,
+    pub token_program0: Pubkey,
+    pub token_program1: Pubkey,
+    // Has to co-sign every swap of restricted markets
+    pub swap_authority: Option<Pubkey>,
+}
+
+impl SwapAccounts {
+    pub fn new(market_address: &Pubkey, market: &Market, token_program0: &Pubkey, token_program1: &Pubkey) -> Self {
+        Self {
+            market: *market_address,
+            config: market.config,
+            token_mint0: market.token_mint0,
+            token_mint1: market.token_mint1,
+            reserve0: market.reserve0,
+            reserve1: market.reserve1,
+            token_program0: *token_program0,
+            token_program1: *token_program1,
+            swap_authority: market.swap_authority,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SwapOptions {
+    pub compute_unit_limit: Option<u32>,
+    pub priority_fee_micro_lamports: Option<u64>,
+}
+
+pub fn build_swap_instruction(accounts: &SwapAccounts, user: &Pubkey, parameters: &SwapParameters) -> Instruction {
+    let program_id = Pubkey::from_str_const(TOKENMILL_PROGRAM);
+
+    let user_token_account0 = get_associated_token_address(user, &accounts.token_mint0, &accounts.token_program0);
+    let user_token_account1 = get_associated_token_address(user, &accounts.token_mint1, &accounts.token_program1);
+    // The program id stands in for the optional account on unrestricted markets
+    let swap_authority = match accounts.swap_authority {
+        Some(swap_authority) => AccountMeta::new_readonly(swap_authority, true),
+        None => AccountMeta::new_readonly(program_id, false),
+    };
+
+    let instruction_accounts = vec![
+        AccountMeta::new_readonly(accounts.config, false), //#0 config
+        AccountMeta::new(accounts.market, false), //#1 market
+        AccountMeta::new_readonly(accounts.token_mint0, false), //#2 token mint 0
+        AccountMeta::new_readonly(accounts.token_mint1, false), //#3 token mint 1
+        AccountMeta::new(accounts.reserve0, false), //#4 market reserve 0
+        AccountMeta::new(accounts.reserve1, false), //#5 market reserve 1
+        AccountMeta::new(user_token_account0, false), //#6 user token account 0
+        AccountMeta::new(user_token_account1, false), //#7 user token account 1
+        AccountMeta::new(Pubkey::from_str_const(TOKENMILL_PROTOCOL_FEE_RESERVE), false), //#8 protocol fee reserve
+        swap_authority, //#9 swap authority
+        AccountMeta::new(*user, true), //#10 user
+        AccountMeta::new_readonly(accounts.token_program0, false), //#11 token program 0
+        AccountMeta::new_readonly(accounts.token_program1, false), //#12 token program 1
+        AccountMeta::new_readonly(find_event_authority(), false), //#13 event authority
+        AccountMeta::new_readonly(program_id, false), //#14 program
+    ];
+
+    let mut data = SWAP_DISCRIMINATOR.to_vec();
+    // Borsh serialization into a vec can't fail
+    parameters.serialize(&mut data).unwrap();
+
+    Instruction {
+        program_id,
+        accounts: instruction_accounts,
+        data,
+    }
+}
+
+// Builds every instruction needed to run the swap from a plain wallet:
+// compute budget, token accounts creation, SOL wrapping and unwrapping when token 1 is wSOL
+pub fn build_swap_instructions(
+    accounts: &SwapAccounts,
+    user: &Pubkey,
+    parameters: &SwapParameters,
+    options: &SwapOptions,
+) -> Vec<Instruction> {
+    let mut instructions = vec![];
+
+    if let Some(compute_unit_limit) = options.compute_unit_limit {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit));
+    }
+    if let Some(priority_fee) = options.priority_fee_micro_lamports {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_price(priority_fee));
+    }
+
+    instructions.push(create_associated_token_account_idempotent(
+        user,
+        user,
+        &accounts.token_mint0,
+        &accounts.token_program0,
+    ));
+    instructions.push(create_associated_token_account_idempotent(
+        user,
+        user,
+        &accounts.token_mint1,
+        &accounts.token_program1,
+    ));
+
+    let wrap_sol = is_native_mint(&accounts.token_mint1);
+    let wsol_account = get_associated_token_address(user, &accounts.token_mint1, &accounts.token_program1);
+
+    if wrap_sol && !parameters.zero_for_one() {
+        // Exact in buys spend `amount`, exact out buys spend at most `threshold`
+        let lamports = if parameters.is_exact_in() {
+            parameters.amount()
+        } else {
+            parameters.threshold()
+        };
+        instructions.push(system_instruction::transfer(user, &wsol_account, lamports));
+        instructions.push(sync_native(&accounts.token_program1, &wsol_account).unwrap());
+    }
+
+    instructions.push(build_swap_instruction(accounts, user, parameters));
+
+    if wrap_sol {
+        instructions.push(
+            close_account(&accounts.token_program1, &wsol_account, user, user, &[user]).unwrap(),
+        );
+    }
+
+    instructions
+}
+
+// The fee payer can differ from the user, e.g. when a service sponsors the transaction fees
+// On restricted markets the swap authority is a required signer too, see `authority`
+pub fn build_swap_transaction(
+    accounts: &SwapAccounts,
+    user: &Pubkey,
+    fee_payer: &Pubkey,
+    parameters: &SwapParameters,
+    options: &SwapOptions,
+) -> Transaction {
+    let instructions = build_swap_instructions(accounts, user, parameters, options);
+    let message = Message::new(&instructions, Some(fee_payer));
+
+    Transaction::new_unsigned(message)
+}
@@ -1,14 +1,37 @@
-use anyhow::{Result, anyhow};
 use ruint::aliases::U256;
 
+use crate::error::SwapMathError;
 use crate::math::{mul_div, mul_div_round_up};
-// use token_mill_v2_client::errors::TokenMillV2Error::*;
 
-type GetAmountFn = fn(u128, u128, u128, bool) -> Result<u128>;
+type Result<T> = std::result::Result<T, SwapMathError>;
+
+type GetAmountFn = fn(&Liquidity, u128, u128, bool) -> Result<u128>;
 
 pub const MAX_FEE_U128: u128 = 1_000_000;
 pub const SQRT_PRICE_SHIFT: usize = 96;
 
+// Liquidity widened once, along with its value shifted by the sqrt price precision,
+// as every amount computation needs one or the other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Liquidity {
+    pub value: u128,
+    wide: U256,
+    shifted: U256,
+}
+
+impl Liquidity {
+    pub fn new(value: u128) -> Self {
+        let wide = U256::from(value);
+
+        Self {
+            value,
+            wide,
+            // Can't saturate, as the value is at most 2^128
+            shifted: wide << SQRT_PRICE_SHIFT,
+        }
+    }
+}
+
 pub fn get_delta_amounts(
     sqrt_price: u128,
     target_sqrt_price: u128,
@@ -16,166 +39,25 @@ pub fn get_delta_amounts(
     delta_amount: i64,
     fee: u32,
 ) -> Result<(u128, u64, u64, u64)> {
-    // Returns the new sqrt price, amount in, amount out and fee amount
-    let (new_sqrt_price, amount_in, amount_out, fee_amount): (u128, u64, u64, u64);
-
-    let zero_for_one = target_sqrt_price < sqrt_price;
-
-    let (get_amount_in, get_amount_out): (GetAmountFn, GetAmountFn) = if zero_for_one {
-        (get_amount_0, get_amount_1)
-    } else {
-        (get_amount_1, get_amount_0)
-    };
-
-    if delta_amount.is_positive() {
-        let delta_amount = delta_amount.unsigned_abs();
-        let amount_in_available =
-            (u128::from(delta_amount) * (MAX_FEE_U128 - u128::from(fee))) / MAX_FEE_U128;
-
-        // If the amount overflows, that means we won't be able to reach the target price
-        // `max_amount_in` is set to `u128::MAX` so that it will always be bigger than `amount_in_available`
-        let max_amount_in =
-            get_amount_in(sqrt_price, target_sqrt_price, liquidity, true).or_else(|err| {
-                if err.to_string().contains("AmountOverflow")
-                {
-                    Ok(u128::MAX)
-                } else {
-                    Err(err)
-                }
-            })?;
-
-        if max_amount_in > amount_in_available {
-            new_sqrt_price = if zero_for_one {
-                get_next_sqrt_ratio_from_amount_0(
-                    sqrt_price,
-                    liquidity,
-                    i64::try_from(amount_in_available).map_err(|_| anyhow!("AmountInOverflow"))?,
-                )?
-            } else {
-                get_next_sqrt_ratio_from_amount_1(
-                    sqrt_price,
-                    liquidity,
-                    i64::try_from(amount_in_available).map_err(|_| anyhow!("AmountInOverflow"))?,
-                )?
-            };
-
-            amount_in = get_amount_in(sqrt_price, new_sqrt_price, liquidity, true)?
-                .try_into()
-                .map_err(|_| anyhow!("AmountInOverflow"))?;
-            fee_amount = delta_amount - amount_in;
-        } else {
-            new_sqrt_price = target_sqrt_price;
-            // Safe cast as max_amount_in <= amount_in_available
-            amount_in = max_amount_in as u64;
-
-            fee_amount = u64::try_from(
-                (max_amount_in * u128::from(fee)).div_ceil(MAX_FEE_U128 - u128::from(fee)),
-            )
-            .map_err(|_| anyhow!("FeeAmountOverflow"))?;
-        }
-
-        amount_out = get_amount_out(sqrt_price, new_sqrt_price, liquidity, false)?
-            .try_into()
-            .map_err(|_| anyhow!("AmountOutOverflow"))?;
-    } else {
-        if delta_amount == 0 {
-            return Ok((sqrt_price, 0, 0, 0));
-        };
-
-        let amount_out_to_fill = delta_amount.unsigned_abs();
-
-        // If the amount overflows, that means we won't be able to reach the target price
-        // `max_amount_out` is set to `u128::MAX` so that it will always be bigger than `amount_out_to_fill`
-        let max_amount_out = get_amount_out(sqrt_price, target_sqrt_price, liquidity, false)
-            .or_else(|err| {
-                if err.to_string().contains("AmountOverflow")
-                {
-                    Ok(u128::MAX)
-                } else {
-                    Err(err)
-                }
-            })?;
-
-        if max_amount_out > amount_out_to_fill.into() {
-            new_sqrt_price = if zero_for_one {
-                get_next_sqrt_ratio_from_amount_1(sqrt_price, liquidity, delta_amount)?
-            } else {
-                get_next_sqrt_ratio_from_amount_0(sqrt_price, liquidity, delta_amount)?
-            };
-            amount_out = amount_out_to_fill;
-        } else {
-            new_sqrt_price = target_sqrt_price;
-            // Safe cast as max_amount_out <= amount_out_to_fill
-            amount_out = max_amount_out as u64;
-        }
-
-        amount_in = get_amount_in(sqrt_price, new_sqrt_price, liquidity, true)?
-            .try_into()
-            .map_err(|_| anyhow!("AmountInOverflow"))?;
-
-        fee_amount = u64::try_from(
-            (u128::from(amount_in) * u128::from(fee)).div_ceil(MAX_FEE_U128 - u128::from(fee)),
-        )
-        .map_err(|_| anyhow!("FeeAmountOverflow"))?;
-    }
-
-    Ok((new_sqrt_price, amount_in, amount_out, fee_amount))
+    Liquidity::new(liquidity).get_delta_amounts(sqrt_price, target_sqrt_price, delta_amount, fee)
 }
 
-// Returns an u128, as it could be used with an "infinite" sqrt price limit
-// Amount is downcasted safely inside `get_delta_amounts` if necessary
 pub fn get_amount_0(
     sqrt_price_a: u128,
     sqrt_price_b: u128,
     liquidity: u128,
     adding: bool,
 ) -> Result<u128> {
-    let (sqrt_price_a, sqrt_price_b) = if sqrt_price_a < sqrt_price_b {
-        (sqrt_price_a, sqrt_price_b)
-    } else {
-        (sqrt_price_b, sqrt_price_a)
-    };
-
-    if adding {
-        mul_div_round_up(
-            U256::from(liquidity).saturating_shl(SQRT_PRICE_SHIFT),
-            U256::from(sqrt_price_b - sqrt_price_a),
-            U256::from(sqrt_price_a) * U256::from(sqrt_price_b),
-        )
-    } else {
-        mul_div(
-            U256::from(liquidity).saturating_shl(SQRT_PRICE_SHIFT),
-            U256::from(sqrt_price_b - sqrt_price_a),
-            U256::from(sqrt_price_a) * U256::from(sqrt_price_b),
-        )
-    }
+    Liquidity::new(liquidity).get_amount_0(sqrt_price_a, sqrt_price_b, adding)
 }
 
-// Returns an u128, as it could be used with an "infinite" sqrt price limit
-// Amount is downcasted safely inside `get_delta_amounts` if necessary
 pub fn get_amount_1(
     sqrt_price_a: u128,
     sqrt_price_b: u128,
     liquidity: u128,
     adding: bool,
 ) -> Result<u128> {
-    let (sqrt_price_a, sqrt_price_b) = if sqrt_price_a < sqrt_price_b {
-        (sqrt_price_a, sqrt_price_b)
-    } else {
-        (sqrt_price_b, sqrt_price_a)
-    };
-
-    if adding {
-        (U256::from(liquidity) * U256::from(sqrt_price_b - sqrt_price_a))
-            .div_ceil(U256::from(2u128.pow(SQRT_PRICE_SHIFT as u32)))
-            .try_into()
-            .map_err(|_| anyhow!("AmountOverflow"))
-    } else {
-        ((U256::from(liquidity) * U256::from(sqrt_price_b - sqrt_price_a))
-            .wrapping_shr(SQRT_PRICE_SHIFT))
-        .try_into()
-        .map_err(|_| anyhow!("AmountOverflow"))
-    }
+    Liquidity::new(liquidity).get_amount_1(sqrt_price_a, sqrt_price_b, adding)
 }
 
 pub fn get_next_sqrt_ratio_from_amount_0(
@@ -183,20 +65,7 @@ pub fn get_next_sqrt_ratio_from_amount_0(
     liquidity: u128,
     amount_0: i64,
 ) -> Result<u128> {
-    if amount_0 == 0 {
-        return Ok(sqrt_price);
-    }
-
-    let liquidity = U256::from(liquidity).saturating_shl(SQRT_PRICE_SHIFT);
-
-    let denominator = match amount_0.is_positive() {
-        true => liquidity + U256::from(amount_0) * U256::from(sqrt_price),
-        false => liquidity
-            .checked_sub(U256::from(amount_0.abs()) * U256::from(sqrt_price))
-            .ok_or(anyhow!("LiquidityOverflow0"))?,
-    };
-
-    mul_div_round_up(liquidity, U256::from(sqrt_price), denominator)
+    Liquidity::new(liquidity).get_next_sqrt_ratio_from_amount_0(sqrt_price, amount_0)
 }
 
 pub fn get_next_sqrt_ratio_from_amount_1(
@@ -204,17 +73,184 @@ pub fn get_next_sqrt_ratio_from_amount_1(
     liquidity: u128,
     amount_1: i64,
 ) -> Result<u128> {
-    let numerator = match amount_1.is_positive() {
-        true => {
-            U256::from(sqrt_price) * U256::from(liquidity)
-                + U256::from(amount_1).saturating_shl(SQRT_PRICE_SHIFT)
+    Liquidity::new(liquidity).get_next_sqrt_ratio_from_amount_1(sqrt_price, amount_1)
+}
+
+impl Liquidity {
+    pub fn get_delta_amounts(
+        &self,
+        sqrt_price: u128,
+        target_sqrt_price: u128,
+        delta_amount: i64,
+        fee: u32,
+    ) -> Result<(u128, u64, u64, u64)> {
+        // Returns the new sqrt price, amount in, amount out and fee amount
+        let (new_sqrt_price, amount_in, amount_out, fee_amount): (u128, u64, u64, u64);
+
+        let zero_for_one = target_sqrt_price < sqrt_price;
+
+        let (get_amount_in, get_amount_out): (GetAmountFn, GetAmountFn) = if zero_for_one {
+            (Self::get_amount_0, Self::get_amount_1)
+        } else {
+            (Self::get_amount_1, Self::get_amount_0)
+        };
+
+        if delta_amount.is_positive() {
+            let delta_amount = delta_amount.unsigned_abs();
+            let amount_in_available =
+                (u128::from(delta_amount) * (MAX_FEE_U128 - u128::from(fee))) / MAX_FEE_U128;
+
+            // If the amount overflows, that means we won't be able to reach the target price
+            // `max_amount_in` is set to `u128::MAX` so that it will always be bigger than `amount_in_available`
+            let max_amount_in =
+                get_amount_in(self, sqrt_price, target_sqrt_price, true).or_else(|err| match err {
+                    SwapMathError::AmountOverflow => Ok(u128::MAX),
+                    err => Err(err),
+                })?;
+
+            if max_amount_in > amount_in_available {
+                // Safe cast as `amount_in_available` is at most `delta_amount`
+                new_sqrt_price = if zero_for_one {
+                    self.get_next_sqrt_ratio_from_amount_0(sqrt_price, amount_in_available as i64)?
+                } else {
+                    self.get_next_sqrt_ratio_from_amount_1(sqrt_price, amount_in_available as i64)?
+                };
+
+                amount_in = get_amount_in(self, sqrt_price, new_sqrt_price, true)?
+                    .try_into()
+                    .map_err(|_| SwapMathError::AmountInOverflow)?;
+                fee_amount = delta_amount - amount_in;
+            } else {
+                new_sqrt_price = target_sqrt_price;
+                // Safe cast as max_amount_in <= amount_in_available
+                amount_in = max_amount_in as u64;
+
+                fee_amount = u64::try_from(
+                    (max_amount_in * u128::from(fee)).div_ceil(MAX_FEE_U128 - u128::from(fee)),
+                )
+                .map_err(|_| SwapMathError::FeeAmountOverflow)?;
+            }
+
+            amount_out = get_amount_out(self, sqrt_price, new_sqrt_price, false)?
+                .try_into()
+                .map_err(|_| SwapMathError::AmountOutOverflow)?;
+        } else {
+            if delta_amount == 0 {
+                return Ok((sqrt_price, 0, 0, 0));
+            };
+
+            let amount_out_to_fill = delta_amount.unsigned_abs();
+
+            // If the amount overflows, that means we won't be able to reach the target price
+            // `max_amount_out` is set to `u128::MAX` so that it will always be bigger than `amount_out_to_fill`
+            let max_amount_out = get_amount_out(self, sqrt_price, target_sqrt_price, false)
+                .or_else(|err| match err {
+                    SwapMathError::AmountOverflow => Ok(u128::MAX),
+                    err => Err(err),
+                })?;
+
+            if max_amount_out > amount_out_to_fill.into() {
+                new_sqrt_price = if zero_for_one {
+                    self.get_next_sqrt_ratio_from_amount_1(sqrt_price, delta_amount)?
+                } else {
+                    self.get_next_sqrt_ratio_from_amount_0(sqrt_price, delta_amount)?
+                };
+                amount_out = amount_out_to_fill;
+            } else {
+                new_sqrt_price = target_sqrt_price;
+                // Safe cast as max_amount_out <= amount_out_to_fill
+                amount_out = max_amount_out as u64;
+            }
+
+            amount_in = get_amount_in(self, sqrt_price, new_sqrt_price, true)?
+                .try_into()
+                .map_err(|_| SwapMathError::AmountInOverflow)?;
+
+            fee_amount = u64::try_from(
+                (u128::from(amount_in) * u128::from(fee)).div_ceil(MAX_FEE_U128 - u128::from(fee)),
+            )
+            .map_err(|_| SwapMathError::FeeAmountOverflow)?;
+        }
+
+        Ok((new_sqrt_price, amount_in, amount_out, fee_amount))
+    }
+
+    // Returns an u128, as it could be used with an "infinite" sqrt price limit
+    // Amount is downcasted safely inside `get_delta_amounts` if necessary
+    pub fn get_amount_0(&self, sqrt_price_a: u128, sqrt_price_b: u128, adding: bool) -> Result<u128> {
+        let (sqrt_price_a, sqrt_price_b) = if sqrt_price_a < sqrt_price_b {
+            (sqrt_price_a, sqrt_price_b)
+        } else {
+            (sqrt_price_b, sqrt_price_a)
+        };
+
+        let delta_sqrt_price = U256::from(sqrt_price_b - sqrt_price_a);
+        let denominator = U256::from(sqrt_price_a) * U256::from(sqrt_price_b);
+
+        if adding {
+            mul_div_round_up(self.shifted, delta_sqrt_price, denominator)
+        } else {
+            mul_div(self.shifted, delta_sqrt_price, denominator)
+        }
+    }
+
+    // Returns an u128, as it could be used with an "infinite" sqrt price limit
+    // Amount is downcasted safely inside `get_delta_amounts` if necessary
+    pub fn get_amount_1(&self, sqrt_price_a: u128, sqrt_price_b: u128, adding: bool) -> Result<u128> {
+        let (sqrt_price_a, sqrt_price_b) = if sqrt_price_a < sqrt_price_b {
+            (sqrt_price_a, sqrt_price_b)
+        } else {
+            (sqrt_price_b, sqrt_price_a)
+        };
+
+        // Can't overflow as both factors are below 2^128
+        let product = self.wide * U256::from(sqrt_price_b - sqrt_price_a);
+        let amount = product >> SQRT_PRICE_SHIFT;
+
+        // Rounding up is adding one whenever any of the shifted out bits is set
+        let amount = if adding && product.trailing_zeros() < SQRT_PRICE_SHIFT {
+            amount + U256::from(1u8)
+        } else {
+            amount
+        };
+
+        amount.try_into().map_err(|_| SwapMathError::AmountOverflow)
+    }
+
+    pub fn get_next_sqrt_ratio_from_amount_0(&self, sqrt_price: u128, amount_0: i64) -> Result<u128> {
+        if amount_0 == 0 {
+            return Ok(sqrt_price);
         }
-        false => (U256::from(sqrt_price) * U256::from(liquidity))
-            .checked_sub(U256::from(amount_1.abs()).saturating_shl(SQRT_PRICE_SHIFT))
-            .ok_or(anyhow!("LiquidityOverflow1"))?,
-    };
 
-    let sqrt_price_next = numerator / U256::from(liquidity);
+        let sqrt_price = U256::from(sqrt_price);
+        let product = U256::from(amount_0.unsigned_abs()) * sqrt_price;
+
+        let denominator = match amount_0.is_positive() {
+            true => self.shifted + product,
+            false => self
+                .shifted
+                .checked_sub(product)
+                .ok_or(SwapMathError::LiquidityOverflow0)?,
+        };
+
+        mul_div_round_up(self.shifted, sqrt_price, denominator)
+    }
+
+    pub fn get_next_sqrt_ratio_from_amount_1(&self, sqrt_price: u128, amount_1: i64) -> Result<u128> {
+        let product = U256::from(sqrt_price) * self.wide;
+        let amount_1_shifted = U256::from(amount_1.unsigned_abs()) << SQRT_PRICE_SHIFT;
+
+        let numerator = match amount_1.is_positive() {
+            true => product + amount_1_shifted,
+            false => product
+                .checked_sub(amount_1_shifted)
+                .ok_or(SwapMathError::LiquidityOverflow1)?,
+        };
+
+        let sqrt_price_next = numerator
+            .checked_div(self.wide)
+            .ok_or(SwapMathError::DivisionByZero)?;
 
-    sqrt_price_next.try_into().map_err(|_| anyhow!("PriceOverflow").into())
-}
\ No newline at end of file
+        sqrt_price_next.try_into().map_err(|_| SwapMathError::PriceOverflow)
+    }
+}
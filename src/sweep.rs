@@ -0,0 +1,254 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    message::Message,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    transaction::Transaction,
+};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token_2022::instruction::close_account;
+
+use crate::portfolio::{PortfolioPosition, TokenHolding};
+use crate::swap::{build_swap_instruction, quote_swap, QuotedSwap, SwapAccounts, SwapOptions, SwapParameters};
+use crate::token::{get_associated_token_address, is_native_mint};
+
+// Sale of a whole dust position, its token account being closed right after
+#[derive(Clone, Debug)]
+pub struct DustSale {
+    pub holding: TokenHolding,
+    pub accounts: SwapAccounts,
+    pub swap: QuotedSwap,
+}
+
+// Position left as is, with the reason why
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedHolding {
+    pub holding: TokenHolding,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SweepPlan {
+    pub sales: Vec<DustSale>,
+    // Empty token accounts and wSOL accounts, closed once the sales went through
+    pub closes: Vec<TokenHolding>,
+    pub skipped: Vec<SkippedHolding>,
+}
+
+impl SweepPlan {
+    // Rent returned to the wallet, the one of the wSOL account the sales may create excluded
+    pub fn reclaimed_lamports(&self) -> u64 {
+        self.sales
+            .iter()
+            .map(|sale| &sale.holding)
+            .chain(self.closes.iter())
+            .map(rent_lamports)
+            .sum()
+    }
+
+    // SOL wrapped in the closed wSOL accounts before the sweep, what the sales return excluded
+    pub fn unwrapped_lamports(&self) -> u64 {
+        self.closes
+            .iter()
+            .filter(|holding| is_native_mint(&holding.mint))
+            .map(|holding| holding.amount)
+            .sum()
+    }
+
+    pub fn to_json(&self) -> Value {
+        let holding = |holding: &TokenHolding| {
+            json!({
+                "account": holding.address.to_string(),
+                "mint": holding.mint.to_string(),
+                "amount": holding.amount,
+                "lamports": holding.lamports,
+            })
+        };
+
+        json!({
+            "sales": self.sales.iter().map(|sale| json!({
+                "account": sale.holding.address.to_string(),
+                "market": sale.accounts.market.to_string(),
+                "amount": sale.swap.parameters.amount(),
+                "amount_out": sale.swap.quote.amount_out,
+                "threshold": sale.swap.parameters.threshold(),
+                "token_mint1": sale.accounts.token_mint1.to_string(),
+            })).collect::<Vec<_>>(),
+            "closes": self.closes.iter().map(holding).collect::<Vec<_>>(),
+            "skipped": self.skipped.iter().map(|skipped| {
+                let mut value = holding(&skipped.holding);
+                value["reason"] = json!(skipped.reason);
+                value
+            }).collect::<Vec<_>>(),
+            "reclaimed_lamports": self.reclaimed_lamports(),
+            "unwrapped_lamports": self.unwrapped_lamports(),
+        })
+    }
+}
+
+// The lamports of wSOL accounts are the rent plus the wrapped SOL
+fn rent_lamports(holding: &TokenHolding) -> u64 {
+    if is_native_mint(&holding.mint) {
+        holding.lamports.saturating_sub(holding.amount)
+    } else {
+        holding.lamports
+    }
+}
+
+// Sells the positions worth at most `max_value` of their quote token when sold, and closes the token accounts
+// left empty. `token_programs1` holds the program of every quote token, keyed by mint
+// Only associated token accounts are sold, as they're the ones swaps spend from
+pub fn plan_sweep(
+    positions: &[PortfolioPosition],
+    owner: &Pubkey,
+    token_programs1: &HashMap<Pubkey, Pubkey>,
+    max_value: u64,
+    slippage_bps: u64,
+) -> Result<SweepPlan> {
+    let mut plan = SweepPlan::default();
+    let skip = |plan: &mut SweepPlan, holding: &TokenHolding, reason: String| {
+        plan.skipped.push(SkippedHolding { holding: holding.clone(), reason });
+    };
+
+    for position in positions {
+        let holding = &position.holding;
+        if holding.amount == 0 || is_native_mint(&holding.mint) {
+            plan.closes.push(holding.clone());
+            continue;
+        }
+        let (Some((market_address, market)), Some(valuation)) = (&position.market, &position.valuation) else {
+            if let Some(err) = &position.valuation_error {
+                skip(&mut plan, holding, format!("Not valued: {}", err));
+            }
+            continue;
+        };
+
+        if valuation.liquidation_value > max_value {
+            continue;
+        }
+        if market.swap_authority.is_some() {
+            skip(&mut plan, holding, format!("Market {} is restricted", market_address));
+            continue;
+        }
+        if holding.address != get_associated_token_address(owner, &holding.mint, &holding.token_program) {
+            skip(&mut plan, holding, "Not an associated token account".to_string());
+            continue;
+        }
+
+        let parameters = SwapParameters::SellExactIn(holding.amount, 0);
+        let quote = quote_swap(market, &parameters)?;
+        let parameters = parameters.with_slippage(&quote, slippage_bps)?;
+        if parameters.threshold() == 0 {
+            skip(&mut plan, holding, "Returns nothing when sold".to_string());
+            continue;
+        }
+        let token_program1 = token_programs1
+            .get(&market.token_mint1)
+            .ok_or(anyhow!("Token program of {} unknown", market.token_mint1))?;
+
+        plan.sales.push(DustSale {
+            holding: holding.clone(),
+            accounts: SwapAccounts::new(market_address, market, &holding.token_program, token_program1),
+            swap: QuotedSwap { parameters, quote },
+        });
+    }
+
+    // Sales quoted in SOL fill the wSOL account, unwrapped at the end even when there was none
+    let wsol_account = get_associated_token_address(owner, &spl_token::native_mint::id(), &spl_token::id());
+    let sells_for_sol = plan.sales.iter().any(|sale| is_native_mint(&sale.accounts.token_mint1));
+    if sells_for_sol && !plan.closes.iter().any(|holding| holding.address == wsol_account) {
+        plan.closes.push(TokenHolding {
+            address: wsol_account,
+            mint: spl_token::native_mint::id(),
+            token_program: spl_token::id(),
+            amount: 0,
+            lamports: 0,
+        });
+    }
+
+    Ok(plan)
+}
+
+// Sale transactions first, then the ones closing the remaining accounts, to send in that order
+// Each sale gets its own transaction closing its token account right after the swap, so that a failing sale
+// only leaves its own account open
+pub fn build_sweep_transactions(plan: &SweepPlan, owner: &Pubkey, options: &SwapOptions) -> Result<Vec<Transaction>> {
+    // The Token 2022 builder takes accounts of both programs
+    let close = |holding: &TokenHolding| {
+        close_account(&holding.token_program, &holding.address, owner, owner, &[owner])
+            .with_context(|| format!("Failed to close {}", holding.address))
+    };
+
+    let sales = plan
+        .sales
+        .iter()
+        .map(|sale| {
+            let accounts = &sale.accounts;
+            Ok(vec![
+                create_associated_token_account_idempotent(
+                    owner,
+                    owner,
+                    &accounts.token_mint1,
+                    &accounts.token_program1,
+                ),
+                build_swap_instruction(accounts, owner, &sale.swap.parameters),
+                close(&sale.holding)?,
+            ])
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let closes = plan
+        .closes
+        .iter()
+        .map(|holding| Ok(vec![close(holding)?]))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut transactions = vec![];
+    for sale in sales {
+        transactions.extend(pack_instructions(&[sale], owner, options)?);
+    }
+    transactions.extend(pack_instructions(&closes, owner, options)?);
+
+    Ok(transactions)
+}
+
+// Packs groups of instructions into as few transactions as fit in a packet, keeping every group whole
+fn pack_instructions(groups: &[Vec<Instruction>], payer: &Pubkey, options: &SwapOptions) -> Result<Vec<Transaction>> {
+    let mut prefix = vec![];
+    if let Some(compute_unit_limit) = options.compute_unit_limit {
+        prefix.push(ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit));
+    }
+    if let Some(priority_fee) = options.priority_fee_micro_lamports {
+        prefix.push(ComputeBudgetInstruction::set_compute_unit_price(priority_fee));
+    }
+
+    let fits = |instructions: &[Instruction]| -> Result<bool> {
+        let transaction = Transaction::new_unsigned(Message::new(instructions, Some(payer)));
+        Ok(bincode::serialized_size(&transaction)? <= PACKET_DATA_SIZE as u64)
+    };
+
+    let mut transactions = vec![];
+    let mut instructions = prefix.clone();
+    for group in groups {
+        let mut candidate = [instructions.as_slice(), group].concat();
+        if !fits(&candidate)? {
+            if instructions.len() > prefix.len() {
+                transactions.push(Transaction::new_unsigned(Message::new(&instructions, Some(payer))));
+            }
+            candidate = [prefix.as_slice(), group].concat();
+            if !fits(&candidate)? {
+                return Err(anyhow!("Sweep instructions of {} don't fit in a transaction", payer));
+            }
+        }
+        instructions = candidate;
+    }
+    if instructions.len() > prefix.len() {
+        transactions.push(Transaction::new_unsigned(Message::new(&instructions, Some(payer))));
+    }
+
+    Ok(transactions)
+}
@@ -0,0 +1,85 @@
+use anyhow::{anyhow, Result};
+
+use crate::market::Market;
+use crate::price::{price_to_sqrt_price_x96, ui_price_to_price};
+use crate::quote::{PreparedMarket, Quote};
+use crate::sizing::largest_amount;
+use crate::swap::{default_sqrt_price_limit, QuotedSwap, SwapParameters};
+
+// Amounts move the price by discrete steps, so the swap returned is the one ending at the closest price
+// at or above the target: the smallest buy pushing the price to it, or the largest sell not dropping below it
+// Sells convert their fee to token 1 with a second swap which moves the price further, it's accounted for
+pub fn quote_to_sqrt_price(market: &Market, target_sqrt_price: u128) -> Result<QuotedSwap> {
+    let prepared = PreparedMarket::new(&market.settings);
+    let zero_for_one = target_sqrt_price < market.sqrt_price_x96;
+    let sqrt_price_limit = default_sqrt_price_limit(market, zero_for_one);
+
+    if zero_for_one && target_sqrt_price < market.settings.sqrt_price_a_x96 {
+        return Err(anyhow!(
+            "InvalidTargetPrice: {} is below the start of the curve {}",
+            target_sqrt_price,
+            market.settings.sqrt_price_a_x96
+        ));
+    }
+
+    if target_sqrt_price == market.sqrt_price_x96 {
+        return Ok(QuotedSwap {
+            parameters: SwapParameters::BuyExactIn(0, 0),
+            quote: prepared.quote(market.sqrt_price_x96, false, 0, sqrt_price_limit)?,
+        });
+    }
+
+    // Failed quotes only come from amounts too large to be swapped, which are past any reachable target
+    let is_past_target = |amount: u64| -> Option<(bool, Quote)> {
+        let quote = prepared
+            .quote(market.sqrt_price_x96, zero_for_one, amount as i64, sqrt_price_limit)
+            .ok()?;
+        let past_target = if zero_for_one {
+            quote.next_sqrt_price < target_sqrt_price
+        } else {
+            quote.next_sqrt_price >= target_sqrt_price
+        };
+
+        Some((past_target, quote))
+    };
+
+    match is_past_target(i64::MAX as u64) {
+        Some((false, quote)) if zero_for_one => {
+            // Even selling everything stops above the target, the largest useful sell is the one emptying the curve
+            return Ok(QuotedSwap {
+                parameters: SwapParameters::SellExactIn(quote.amount_in, 0),
+                quote: prepared
+                    .quote(market.sqrt_price_x96, zero_for_one, quote.amount_in as i64, sqrt_price_limit)?,
+            });
+        }
+        Some((false, _)) => return Err(anyhow!("TargetUnreachable: {}", target_sqrt_price)),
+        _ => {}
+    }
+    let last_before_target = largest_amount(|amount| matches!(is_past_target(amount), Some((false, _))));
+
+    // Buys need the smallest amount getting to the target, sells the largest one staying above it
+    let amount = if zero_for_one { last_before_target } else { last_before_target + 1 };
+    let quote = prepared.quote(market.sqrt_price_x96, zero_for_one, amount as i64, sqrt_price_limit)?;
+    let parameters = if zero_for_one {
+        SwapParameters::SellExactIn(amount, 0)
+    } else {
+        SwapParameters::BuyExactIn(amount, 0)
+    };
+
+    Ok(QuotedSwap { parameters, quote })
+}
+
+// `price` is in whole token 1 per whole token 0
+pub fn quote_to_price(market: &Market, price: f64, decimals0: u8, decimals1: u8) -> Result<QuotedSwap> {
+    let target_sqrt_price = price_to_sqrt_price_x96(ui_price_to_price(price, decimals0, decimals1))
+        .ok_or(anyhow!("InvalidTargetPrice: {}", price))?;
+
+    quote_to_sqrt_price(market, target_sqrt_price)
+}
+
+// Market cap of the whole supply of token 0, in whole token 1
+pub fn quote_to_market_cap(market: &Market, market_cap: f64, decimals0: u8, decimals1: u8) -> Result<QuotedSwap> {
+    let supply = market.settings.max_supply as f64 / 10f64.powi(i32::from(decimals0));
+
+    quote_to_price(market, market_cap / supply, decimals0, decimals1)
+}
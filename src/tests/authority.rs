@@ -0,0 +1,157 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use axum::{http::StatusCode, response::IntoResponse};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+
+use super::{mid_curve_market, restricted_market};
+use crate::authority::{check_swap_access, cosign_swap_transaction, request_cosign, SwapAllowlist};
+use crate::market::TOKENMILL_PROGRAM;
+use crate::server::{router, swap_cosigner, AppState};
+use crate::store::MarketStore;
+use crate::swap::{build_swap_instruction, build_swap_transaction, SwapAccounts, SwapOptions, SwapParameters};
+
+fn restricted_swap_accounts(swap_authority: &Pubkey) -> (Pubkey, SwapAccounts) {
+    let market = restricted_market(swap_authority);
+    let market_address = Pubkey::new_unique();
+
+    (market_address, SwapAccounts::new(&market_address, &market, &spl_token_2022::id(), &spl_token::id()))
+}
+
+#[test]
+fn swaps_on_restricted_markets_are_signed_by_the_swap_authority() {
+    let (user, swap_authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (_, mut accounts) = restricted_swap_accounts(&swap_authority);
+    let parameters = SwapParameters::BuyExactIn(1_000, 0);
+
+    let restricted = build_swap_instruction(&accounts, &user, &parameters);
+    assert_eq!(restricted.accounts[9].pubkey, swap_authority);
+    assert!(restricted.accounts[9].is_signer && !restricted.accounts[9].is_writable);
+
+    accounts.swap_authority = None;
+    let unrestricted = build_swap_instruction(&accounts, &user, &parameters);
+    assert_eq!(unrestricted.accounts[9].pubkey, Pubkey::from_str_const(TOKENMILL_PROGRAM));
+    assert!(!unrestricted.accounts[9].is_signer);
+}
+
+#[test]
+fn restricted_markets_need_the_swap_authority_among_the_signers() {
+    let (user, swap_authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let market_address = Pubkey::new_unique();
+
+    assert!(check_swap_access(&market_address, &mid_curve_market(), &[user]).is_ok());
+
+    let market = restricted_market(&swap_authority);
+    let err = check_swap_access(&market_address, &market, &[user]).unwrap_err();
+    assert!(err.to_string().starts_with("SwapAuthorityRequired"));
+    assert!(check_swap_access(&market_address, &market, &[user, swap_authority]).is_ok());
+}
+
+#[test]
+fn swap_authority_co_signs_a_swap_the_trader_signed() {
+    let (user, swap_authority) = (Keypair::new(), Keypair::new());
+    let (_, accounts) = restricted_swap_accounts(&swap_authority.pubkey());
+    let parameters = SwapParameters::BuyExactIn(1_000, 0);
+    let mut transaction =
+        build_swap_transaction(&accounts, &user.pubkey(), &user.pubkey(), &parameters, &SwapOptions::default());
+    let recent_blockhash = solana_sdk::hash::Hash::new_unique();
+
+    transaction.partial_sign(&[&user], recent_blockhash);
+    assert!(!transaction.is_signed());
+
+    cosign_swap_transaction(&mut transaction, &swap_authority).unwrap();
+    assert!(transaction.is_signed());
+    transaction.verify().unwrap();
+}
+
+#[test]
+fn swap_authority_only_signs_for_swaps() {
+    let (user, swap_authority) = (Keypair::new(), Keypair::new());
+    let (_, accounts) = restricted_swap_accounts(&swap_authority.pubkey());
+    let swap = build_swap_instruction(&accounts, &user.pubkey(), &SwapParameters::BuyExactIn(1_000, 0));
+
+    // Paying the fees
+    let mut transaction = Transaction::new_with_payer(std::slice::from_ref(&swap), Some(&swap_authority.pubkey()));
+    assert!(cosign_swap_transaction(&mut transaction, &swap_authority).is_err());
+
+    // Moving its lamports along with the swap
+    let transfer = system_instruction::transfer(&swap_authority.pubkey(), &user.pubkey(), 1);
+    let mut transaction = Transaction::new_with_payer(&[swap, transfer], Some(&user.pubkey()));
+    assert!(cosign_swap_transaction(&mut transaction, &swap_authority).is_err());
+
+    // Nothing to approve
+    let transfer = system_instruction::transfer(&user.pubkey(), &swap_authority.pubkey(), 1);
+    let mut transaction = Transaction::new_with_payer(&[transfer], Some(&user.pubkey()));
+    assert!(cosign_swap_transaction(&mut transaction, &swap_authority).is_err());
+}
+
+// Service holding the swap authority, the RPC is never reached as co-signing doesn't fetch anything
+fn cosigning_state(swap_authority: Keypair, allowlist: SwapAllowlist) -> AppState {
+    AppState {
+        store: MarketStore::new("http://127.0.0.1:1".to_string(), CommitmentConfig::processed(), Duration::ZERO),
+        fee_payer: None,
+        swap_authority: Some(swap_authority),
+        swap_allowlist: allowlist,
+    }
+}
+
+#[test]
+fn service_only_co_signs_built_swaps_for_listed_users() {
+    let (user, listed, swap_authority) = (Pubkey::new_unique(), Pubkey::new_unique(), Keypair::new());
+    let (market_address, market) = (Pubkey::new_unique(), restricted_market(&swap_authority.pubkey()));
+    let allowlist = SwapAllowlist {
+        users: HashSet::from([listed]),
+        markets: HashSet::from([market_address]),
+    };
+    let state = cosigning_state(swap_authority.insecure_clone(), allowlist);
+    let status = |err: crate::server::ApiError| err.into_response().status();
+
+    let err = swap_cosigner(&state, &user, &market_address, &market).unwrap_err();
+    assert_eq!(status(err), StatusCode::FORBIDDEN);
+    let cosigner = swap_cosigner(&state, &listed, &market_address, &market).unwrap();
+    assert_eq!(cosigner.map(Keypair::pubkey), Some(swap_authority.pubkey()));
+
+    // The authority itself needs no co-signature, and unrestricted markets are open to anyone
+    assert!(swap_cosigner(&state, &swap_authority.pubkey(), &market_address, &market).unwrap().is_none());
+    assert!(swap_cosigner(&state, &user, &market_address, &mid_curve_market()).unwrap().is_none());
+
+    // Nor does the service sign for markets restricted by another authority
+    let other = restricted_market(&Pubkey::new_unique());
+    let err = swap_cosigner(&state, &listed, &market_address, &other).unwrap_err();
+    assert_eq!(status(err), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn cosign_endpoint_refuses_unlisted_users() {
+    let (user, listed, swap_authority) = (Keypair::new(), Keypair::new(), Keypair::new());
+    let (market_address, accounts) = restricted_swap_accounts(&swap_authority.pubkey());
+    let allowlist = SwapAllowlist {
+        users: HashSet::from([listed.pubkey()]),
+        markets: HashSet::from([market_address]),
+    };
+    let state = Arc::new(cosigning_state(swap_authority.insecure_clone(), allowlist));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router(state)).await });
+
+    let client = reqwest::Client::new();
+    let parameters = SwapParameters::BuyExactIn(1_000, 0);
+    let transaction = |user: &Keypair| {
+        let mut transaction =
+            build_swap_transaction(&accounts, &user.pubkey(), &user.pubkey(), &parameters, &SwapOptions::default());
+        transaction.partial_sign(&[user], solana_sdk::hash::Hash::new_unique());
+        transaction
+    };
+
+    let err = request_cosign(&client, &url, &transaction(&user)).await.unwrap_err();
+    assert!(err.to_string().starts_with("CosignFailed: 403"));
+
+    let cosigned = request_cosign(&client, &url, &transaction(&listed)).await.unwrap();
+    cosigned.verify().unwrap();
+}
@@ -0,0 +1,148 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+
+use super::market_with;
+use crate::backtest::{parse_events_csv, parse_events_json, run_backtest, Backtest, Order, SnipeAndExit, Strategy, SwapEvent};
+use crate::market::{Market, MarketSettings};
+use crate::state::Fill;
+use crate::swap::SwapParameters;
+
+fn settings() -> MarketSettings {
+    MarketSettings {
+        max_supply: 1_000_000_000_000_000,
+        sqrt_price_a_x96: 79_228_162_514_264_337_593_543_950,
+        sqrt_price_b_x96: 237_684_487_542_793_012_780_631_850,
+        liquidity_a: 1_200_000_000_000,
+        liquidity_b: 4_000_000_000_000,
+        fee: 10_000,
+    }
+}
+
+// The first swap carries the market, the next ones only their parameters
+fn events(market: &Market, swaps: &[SwapParameters]) -> Vec<SwapEvent> {
+    let address = Pubkey::new_unique();
+
+    swaps
+        .iter()
+        .enumerate()
+        .map(|(index, parameters)| SwapEvent {
+            slot: 100 + index as u64,
+            signature: format!("swap{}", index),
+            market: address,
+            parameters: parameters.clone(),
+            market_data: (index == 0).then(|| borsh::to_vec(market).unwrap()),
+        })
+        .collect()
+}
+
+struct Hold;
+
+impl Strategy for Hold {
+    fn on_swap(&mut self, _event: &SwapEvent, _fill: Option<&Fill>, _backtest: &Backtest) -> Vec<Order> {
+        vec![]
+    }
+}
+
+#[test]
+fn replaying_without_orders_only_counts_the_swaps() {
+    let market = market_with(settings(), settings().sqrt_price_a_x96);
+    let events = events(
+        &market,
+        &[
+            SwapParameters::BuyExactIn(1_000_000_000, 0),
+            // No threshold, so the program would reject it
+            SwapParameters::BuyExactOut(1_000_000, 0),
+            SwapParameters::SellExactIn(1_000_000, 0),
+        ],
+    );
+
+    let report = run_backtest(&events, &mut Hold).unwrap();
+
+    assert_eq!((report.swaps, report.reverted), (3, 1));
+    assert!(report.fills.is_empty() && report.positions.is_empty());
+    assert_eq!((report.pnl, report.max_drawdown), (0, 0));
+}
+
+#[test]
+fn snipe_takes_profit_once_the_market_rises() {
+    let market = market_with(settings(), settings().sqrt_price_a_x96);
+    let events = events(
+        &market,
+        &[
+            SwapParameters::BuyExactIn(1_000_000, 0),
+            SwapParameters::BuyExactIn(1_000_000_000, 0),
+            SwapParameters::BuyExactIn(1_000_000_000, 0),
+        ],
+    );
+    let mut strategy = SnipeAndExit::new(10_000_000, 1_000, 1_000);
+
+    let report = run_backtest(&events, &mut strategy).unwrap();
+
+    assert_eq!(report.fills.len(), 2);
+    let (buy, sell) = (&report.fills[0], &report.fills[1]);
+    assert_eq!((buy.slot, sell.slot), (100, 101));
+    assert!(!buy.fill.parameters.zero_for_one() && sell.fill.parameters.zero_for_one());
+    assert!(buy.slippage_bps > 0.0 && sell.slippage_bps > 0.0);
+
+    let position = report.positions.values().next().unwrap();
+    assert_eq!(position.tokens, 0);
+    assert_eq!(report.pnl, i128::from(position.received) - i128::from(position.spent));
+    assert!(report.pnl > 0);
+}
+
+#[test]
+fn snipe_stops_the_loss_once_the_market_falls() {
+    let market = market_with(settings(), settings().sqrt_price_b_x96);
+    let events = events(
+        &market,
+        &[
+            SwapParameters::SellExactIn(1_000_000, 0),
+            SwapParameters::SellExactIn(300_000_000_000_000, 0),
+            SwapParameters::SellExactIn(300_000_000_000_000, 0),
+        ],
+    );
+    let mut strategy = SnipeAndExit::new(10_000_000, 1_000, 1_000);
+
+    let report = run_backtest(&events, &mut strategy).unwrap();
+
+    assert_eq!(report.fills.len(), 2);
+    assert!(report.pnl < 0);
+    assert!(report.max_drawdown >= report.pnl.unsigned_abs());
+    // Nothing left to sell
+    assert_eq!(report.positions.values().next().unwrap().tokens, 0);
+}
+
+#[test]
+fn first_swap_of_a_market_needs_its_data() {
+    let market = market_with(settings(), settings().sqrt_price_a_x96);
+    let mut events = events(&market, &[SwapParameters::BuyExactIn(1_000, 0)]);
+    events[0].market_data = None;
+
+    let err = run_backtest(&events, &mut Hold).unwrap_err();
+    assert!(err.to_string().starts_with("MissingMarketData"));
+}
+
+#[test]
+fn events_parse_from_csv_and_json() {
+    let market = market_with(settings(), settings().sqrt_price_a_x96);
+    let events = events(
+        &market,
+        &[SwapParameters::BuyExactIn(1_000, 0), SwapParameters::SellExactOut(500, 2_000)],
+    );
+    let market_data = general_purpose::STANDARD.encode(borsh::to_vec(&market).unwrap());
+
+    let csv = format!(
+        "slot,signature,market,side,mode,amount,threshold,market_data\n\
+         100,swap0,{market},buy,exact_in,1000,0,{market_data}\n\
+         101,swap1,{market},sell,exact_out,500,2000,\n",
+        market = events[0].market,
+    );
+    assert_eq!(parse_events_csv(&csv).unwrap(), events);
+
+    let value = json!([
+        { "slot": 100, "signature": "swap0", "market": events[0].market.to_string(), "side": "buy", "amount": 1000, "threshold": 0, "market_data": market_data },
+        { "slot": 101, "signature": "swap1", "market": events[0].market.to_string(), "side": "sell", "mode": "exact_out", "amount": 500, "threshold": 2000 },
+    ]);
+    assert_eq!(parse_events_json(&value).unwrap(), events);
+}
@@ -0,0 +1,54 @@
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, transaction::Transaction};
+
+use super::mid_curve_market;
+use crate::bundle::{build_round_trip_bundle, plan_round_trip, BundleCosts};
+use crate::swap::SwapAccounts;
+
+fn bundle_costs() -> BundleCosts {
+    BundleCosts {
+        tip_lamports: 10_000,
+        compute_unit_limit: 200_000,
+        priority_fee_micro_lamports: 1_000,
+    }
+}
+
+#[test]
+fn round_trips_losing_more_than_allowed_are_refused() {
+    let mut market = mid_curve_market();
+    market.token_mint1 = spl_token::native_mint::id();
+    let costs = bundle_costs();
+
+    // Fees are paid both ways and nothing comes in between, so a round trip always loses
+    let err = plan_round_trip(&market, 100_000_000, 100, &costs, 0).unwrap_err();
+    assert!(err.to_string().starts_with("UnprofitableBundle"));
+
+    let round_trip = plan_round_trip(&market, 100_000_000, 100, &costs, -10_000_000).unwrap();
+    assert!(round_trip.worst_net <= round_trip.expected_net && round_trip.expected_net < 0);
+    assert!(round_trip.worst_net >= -10_000_000);
+    // The sell can't spend more than the buy is guaranteed to return
+    assert_eq!(round_trip.sell.parameters.amount(), round_trip.buy.parameters.threshold());
+    assert_eq!(
+        round_trip.expected_net,
+        i128::from(round_trip.sell.quote.amount_out) - i128::from(round_trip.buy.quote.amount_in) - i128::from(costs.total())
+    );
+
+    market.token_mint1 = Pubkey::new_unique();
+    assert!(plan_round_trip(&market, 100_000_000, 100, &costs, -10_000_000).is_err());
+}
+
+#[test]
+fn round_trip_bundle_tips_in_its_last_transaction() {
+    let mut market = mid_curve_market();
+    market.token_mint1 = spl_token::native_mint::id();
+    let accounts = SwapAccounts::new(&Pubkey::new_unique(), &market, &spl_token_2022::id(), &spl_token::id());
+    let round_trip = plan_round_trip(&market, 100_000_000, 100, &bundle_costs(), -10_000_000).unwrap();
+    let (wallet, tip_account) = (Keypair::new(), Pubkey::new_unique());
+
+    let transactions =
+        build_round_trip_bundle(&accounts, &round_trip, &wallet, &tip_account, solana_sdk::hash::Hash::new_unique());
+
+    assert_eq!(transactions.len(), 2);
+    assert!(transactions.iter().all(|transaction| transaction.verify().is_ok()));
+    let tipped = |transaction: &Transaction| transaction.message.account_keys.contains(&tip_account);
+    assert!(!tipped(&transactions[0]) && tipped(&transactions[1]));
+}
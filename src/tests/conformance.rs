@@ -0,0 +1,119 @@
+use borsh::BorshSerialize;
+use solana_sdk::pubkey::Pubkey;
+use std::path::Path;
+
+use super::market_with;
+use crate::fixtures::{load_fixtures, SwapFixture, FIXTURES_DIR};
+use crate::market::MarketSettings;
+use crate::price::is_phase_a;
+use crate::swap::{default_sqrt_price_limit, quote_swap, SwapParameters};
+use crate::swap_math::get_delta_amounts;
+
+// Vectors are recorded from mainnet with `fixtures record <market>`
+fn fixtures() -> Vec<SwapFixture> {
+    load_fixtures(&Path::new(env!("CARGO_MANIFEST_DIR")).join(FIXTURES_DIR)).unwrap()
+}
+
+#[test]
+#[ignore = "needs swap vectors covering both phases and a boundary crossing, recorded with `fixtures record`"]
+fn recorded_swaps_cover_both_phases_and_the_boundary() {
+    let (mut phase_a, mut phase_b, mut crossing) = (false, false, false);
+    for fixture in fixtures() {
+        let market = fixture.market().unwrap();
+        let quote = quote_swap(&market, &fixture.parameters).unwrap();
+
+        crossing |= quote.crossed_phase_boundary;
+        if !quote.crossed_phase_boundary {
+            phase_a |= is_phase_a(&market, market.sqrt_price_x96);
+            phase_b |= !is_phase_a(&market, market.sqrt_price_x96);
+        }
+    }
+
+    assert!(phase_a && phase_b && crossing, "phase A: {}, phase B: {}, crossing: {}", phase_a, phase_b, crossing);
+}
+
+#[test]
+fn recorded_swaps_match_the_quote() {
+    for fixture in fixtures() {
+        let market = fixture.market().unwrap();
+        let quote = quote_swap(&market, &fixture.parameters)
+            .unwrap_or_else(|err| panic!("{}: {}", fixture.signature, err));
+
+        assert_eq!(quote.amount_in, fixture.amount_in, "{}", fixture.signature);
+        assert_eq!(quote.amount_out, fixture.amount_out, "{}", fixture.signature);
+        if let Some(sqrt_price_x96) = fixture.sqrt_price_x96 {
+            assert_eq!(quote.next_sqrt_price, sqrt_price_x96, "{}", fixture.signature);
+        }
+    }
+}
+
+// Swaps filled without leaving the phase they started in are a single `get_delta_amounts` call
+#[test]
+fn recorded_swaps_within_a_phase_match_get_delta_amounts() {
+    for fixture in fixtures() {
+        let market = fixture.market().unwrap();
+        let settings = &market.settings;
+        let zero_for_one = fixture.parameters.zero_for_one();
+        let sqrt_price_limit = default_sqrt_price_limit(&market, zero_for_one);
+
+        let (liquidity, target_sqrt_price) = match (is_phase_a(&market, market.sqrt_price_x96), zero_for_one) {
+            (true, false) => (settings.liquidity_a, sqrt_price_limit.min(settings.sqrt_price_b_x96)),
+            (true, true) => (settings.liquidity_a, sqrt_price_limit),
+            (false, false) => (settings.liquidity_b, sqrt_price_limit),
+            (false, true) => (settings.liquidity_b, sqrt_price_limit.max(settings.sqrt_price_b_x96)),
+        };
+
+        let (_, amount_in, amount_out, fee_amount) = get_delta_amounts(
+            market.sqrt_price_x96,
+            target_sqrt_price,
+            liquidity,
+            fixture.parameters.delta_amount().unwrap(),
+            settings.fee,
+        )
+        .unwrap_or_else(|err| panic!("{}: {}", fixture.signature, err));
+
+        let filled = if fixture.parameters.is_exact_in() {
+            amount_in + fee_amount == fixture.parameters.amount()
+        } else {
+            amount_out == fixture.parameters.amount()
+        };
+        if !filled {
+            continue;
+        }
+
+        assert_eq!(amount_in + fee_amount, fixture.amount_in, "{}", fixture.signature);
+        assert_eq!(amount_out, fixture.amount_out, "{}", fixture.signature);
+    }
+}
+
+#[test]
+fn fixtures_round_trip_through_json() {
+    let settings = MarketSettings {
+        max_supply: 1_000_000_000_000_000,
+        sqrt_price_a_x96: 1 << 90,
+        sqrt_price_b_x96: 1 << 91,
+        liquidity_a: 1 << 60,
+        liquidity_b: 1 << 58,
+        fee: 10_000,
+    };
+    let mut market_data = vec![];
+    market_with(settings, (1 << 90) + (1 << 80)).serialize(&mut market_data).unwrap();
+
+    let fixture = SwapFixture {
+        signature: "5h6xBEauJ3PK6SWCZ1PGjBvj8vDdWG3KpwATGy1ARAXFSDwt8GFXM7W5Ncn16wmqokgpiKRLuS83KUxyZyv2sUYv".to_string(),
+        slot: 312_345_678,
+        market_address: Pubkey::new_unique(),
+        market_data,
+        parameters: SwapParameters::SellExactOut(1_000_000, u64::MAX),
+        amount_in: 123_456_789,
+        amount_out: 1_000_000,
+        sqrt_price_x96: Some(u128::MAX),
+    };
+
+    assert_eq!(SwapFixture::from_json(&fixture.to_json()).unwrap(), fixture);
+    assert_eq!(
+        SwapFixture::from_json(&SwapFixture { sqrt_price_x96: None, ..fixture.clone() }.to_json()).unwrap(),
+        SwapFixture { sqrt_price_x96: None, ..fixture }
+    );
+    assert!(SwapFixture::from_json(&serde_json::json!({})).is_err());
+}
@@ -0,0 +1,72 @@
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use super::curve_settings;
+use crate::create_market::{
+    build_create_market_instruction, build_create_market_instructions, CreateMarketAccounts, CreateMarketArgs,
+    MintStandard, CREATE_MARKET_DISCRIMINATOR, CREATE_MARKET_WITH_SPL_DISCRIMINATOR,
+};
+use crate::market::{Market, TOKENMILL_PROGRAM};
+use crate::swap::SwapOptions;
+use crate::token::get_associated_token_address;
+
+fn create_market_args() -> CreateMarketArgs {
+    CreateMarketArgs {
+        name: "Token".to_string(),
+        symbol: "TKN".to_string(),
+        uri: "https://example.com/token.json".to_string(),
+        settings: curve_settings(),
+        swap_authority: None,
+    }
+}
+
+#[test]
+fn create_market_derives_the_market_and_its_reserves() {
+    let (config, mint0, mint1) = (Pubkey::new_unique(), Pubkey::new_unique(), spl_token::native_mint::id());
+    let creator = Pubkey::new_unique();
+    let accounts = CreateMarketAccounts::new(&config, &mint0, &mint1, MintStandard::Token2022, &spl_token::id());
+    let args = create_market_args();
+
+    let instruction = build_create_market_instruction(&accounts, &creator, &args);
+    let market = Market::find_pda(&mint0).0;
+
+    assert_eq!(instruction.program_id, Pubkey::from_str_const(TOKENMILL_PROGRAM));
+    assert_eq!(instruction.accounts[1].pubkey, market);
+    assert_eq!(instruction.accounts[4].pubkey, get_associated_token_address(&market, &mint0, &spl_token_2022::id()));
+    assert_eq!(instruction.accounts[5].pubkey, get_associated_token_address(&market, &mint1, &spl_token::id()));
+
+    // Only the creator and the new mint sign
+    let signers = instruction.accounts.iter().filter(|account| account.is_signer).map(|account| account.pubkey);
+    assert_eq!(signers.collect::<Vec<_>>(), vec![mint0, creator]);
+
+    assert_eq!(instruction.data[..8], CREATE_MARKET_DISCRIMINATOR);
+    assert_eq!(CreateMarketArgs::try_from_slice(&instruction.data[8..]).unwrap(), args);
+}
+
+#[test]
+fn create_market_with_spl_adds_the_metadata_accounts() {
+    let (config, mint0, mint1) = (Pubkey::new_unique(), Pubkey::new_unique(), spl_token::native_mint::id());
+    let token2022 = CreateMarketAccounts::new(&config, &mint0, &mint1, MintStandard::Token2022, &spl_token::id());
+    let spl = CreateMarketAccounts::new(&config, &mint0, &mint1, MintStandard::Spl, &spl_token::id());
+
+    let token2022 = build_create_market_instruction(&token2022, &Pubkey::new_unique(), &create_market_args());
+    let spl = build_create_market_instruction(&spl, &Pubkey::new_unique(), &create_market_args());
+
+    assert_eq!(spl.accounts.len(), token2022.accounts.len() + 2);
+    assert_eq!(spl.data[..8], CREATE_MARKET_WITH_SPL_DISCRIMINATOR);
+}
+
+#[test]
+fn create_market_rejects_invalid_settings() {
+    let accounts = CreateMarketAccounts::new(
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        &spl_token::native_mint::id(),
+        MintStandard::Token2022,
+        &spl_token::id(),
+    );
+    let mut args = create_market_args();
+    args.settings.sqrt_price_b_x96 = args.settings.sqrt_price_a_x96;
+
+    assert!(build_create_market_instructions(&accounts, &Pubkey::new_unique(), &args, &SwapOptions::default()).is_err());
+}
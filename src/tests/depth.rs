@@ -0,0 +1,50 @@
+use proptest::prelude::*;
+
+use super::arb_market;
+use crate::depth::{depth_by_price, depth_by_size};
+use crate::price::sqrt_price_x96_to_price;
+use crate::swap::{quote_swap, SwapParameters};
+
+proptest! {
+    #[test]
+    fn depth_ladders_are_cumulative(
+        market in arb_market(),
+        zero_for_one in any::<bool>(),
+        steps_bps in prop::collection::vec(0u64..20_000, 1..8),
+        sizes in prop::collection::vec(0u64..(1u64 << 62), 1..8),
+    ) {
+        let by_price = depth_by_price(&market, zero_for_one, &steps_bps);
+        let by_size = depth_by_size(&market, zero_for_one, &sizes).unwrap();
+
+        for ladder in [&by_price, &by_size] {
+            for levels in ladder.windows(2) {
+                prop_assert!(levels[0].step < levels[1].step);
+                prop_assert!(levels[0].amount_in <= levels[1].amount_in);
+                prop_assert!(levels[0].amount_out <= levels[1].amount_out);
+                prop_assert!(levels[0].fee_amount <= levels[1].fee_amount);
+            }
+        }
+
+        // Each price level moves the price by at least its step, and the swap stopping there is the same as
+        // an exact in swap of the amount it took
+        let price = sqrt_price_x96_to_price(market.sqrt_price_x96);
+        for level in by_price.iter() {
+            let moved = sqrt_price_x96_to_price(level.sqrt_price_x96) / price - 1.0;
+            let step = level.step as f64 / 10_000.0;
+            if zero_for_one {
+                prop_assert!(level.sqrt_price_x96 == market.settings.sqrt_price_a_x96 || -moved >= step * (1.0 - 1e-9));
+            } else {
+                prop_assert!(moved >= step * (1.0 - 1e-9));
+            }
+        }
+        for level in by_size.iter() {
+            let parameters = if zero_for_one {
+                SwapParameters::SellExactIn(level.step, 0)
+            } else {
+                SwapParameters::BuyExactIn(level.step, 0)
+            };
+            let quote = quote_swap(&market, &parameters).unwrap();
+            prop_assert_eq!((quote.amount_in, quote.amount_out), (level.amount_in, level.amount_out));
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use proptest::prelude::*;
+
+use crate::design::{design_curve, validate_settings, CurveDesign};
+use crate::market::MarketSettings;
+use crate::supply::{curve_end_sqrt_price, tokens_sold};
+
+proptest! {
+    #[test]
+    fn designed_curves_sell_their_supplies_between_their_prices(
+        // Raises stay within a u64
+        start_price_exponent in -10i32..-4,
+        boundary_multiple in 1.01f64..100.0,
+        end_multiple in 1.01f64..100.0,
+        supply_a in (1u64 << 30)..(1u64 << 48),
+        supply_b in (1u64 << 30)..(1u64 << 48),
+        decimals0 in 6u8..10,
+        decimals1 in 6u8..10,
+        fee in 0u32..100_000,
+    ) {
+        let design = CurveDesign {
+            start_price: 10f64.powi(start_price_exponent),
+            boundary_price: 10f64.powi(start_price_exponent) * boundary_multiple,
+            end_price: 10f64.powi(start_price_exponent) * boundary_multiple * end_multiple,
+            supply_a,
+            supply_b,
+            decimals0,
+            decimals1,
+            fee,
+        };
+        let curve = design_curve(&design, 10).unwrap();
+        let settings = &curve.settings;
+
+        let sold_a = tokens_sold(settings, settings.sqrt_price_b_x96).unwrap();
+        let sold = tokens_sold(settings, curve.end_sqrt_price_x96).unwrap();
+        // Liquidities being integers, each phase can miss its supply by the share of one unit of liquidity
+        let shortfall = |supply: u64, liquidity: u128| supply as f64 / liquidity as f64 + 1.0;
+        prop_assert!(sold_a <= supply_a && (supply_a - sold_a) as f64 <= shortfall(supply_a, settings.liquidity_a));
+        prop_assert!(sold <= supply_a + supply_b);
+        prop_assert!(
+            (supply_a + supply_b - sold) as f64
+                <= shortfall(supply_a, settings.liquidity_a) + shortfall(supply_b, settings.liquidity_b)
+        );
+        prop_assert!(curve.raise_a <= curve.total_raise);
+
+        // Phase A selling its supply short of the boundary price
+        let mut skewed = settings.clone();
+        skewed.liquidity_a -= skewed.liquidity_a / 100;
+        prop_assert!(validate_settings(&skewed, curve.end_sqrt_price_x96, supply_a).is_err());
+
+        // The program sells out the curve at the designed end price, give or take the rounding of the liquidities
+        let curve_end_sqrt_price_x96 = curve_end_sqrt_price(settings).unwrap();
+        prop_assert!(curve_end_sqrt_price_x96 >= curve.end_sqrt_price_x96);
+        prop_assert_eq!(tokens_sold(settings, curve_end_sqrt_price_x96).unwrap(), settings.max_supply);
+
+        // A max supply the curve sells out of far from its end price
+        for max_supply in [settings.max_supply - supply_b / 100, settings.max_supply + supply_b / 100] {
+            let error = validate_settings(&MarketSettings { max_supply, ..settings.clone() }, curve.end_sqrt_price_x96, supply_a);
+            prop_assert!(error.unwrap_err().to_string().starts_with("CurveMismatch: the curve sells out at"));
+        }
+
+        prop_assert_eq!(curve.curve.len(), 11);
+        prop_assert_eq!(curve.curve[0].sqrt_price_x96, settings.sqrt_price_a_x96);
+        prop_assert_eq!(curve.curve[10].sold, settings.max_supply);
+        for points in curve.curve.windows(2) {
+            prop_assert!(points[0].sqrt_price_x96 <= points[1].sqrt_price_x96);
+            prop_assert!(points[0].raised <= points[1].raised);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+use crate::error::{describe_transaction_error, ProgramError, TokenMillError};
+use crate::market::TOKENMILL_PROGRAM;
+
+#[test]
+fn program_errors_are_decoded_by_name() {
+    for err in TokenMillError::ALL {
+        assert_eq!(TokenMillError::from_code(err.code()), Some(err));
+        assert!(err.to_string().starts_with(&format!("{:?}: ", err)));
+    }
+    assert_eq!(TokenMillError::SlippageExceeded.code(), 0x1771);
+
+    let err = TransactionError::InstructionError(2, InstructionError::Custom(0x1771));
+    assert_eq!(TokenMillError::from_transaction_error(&err), Some(TokenMillError::SlippageExceeded));
+    assert!(describe_transaction_error(&err).contains("SlippageExceeded"));
+    let err = TransactionError::InstructionError(2, InstructionError::Custom(1));
+    assert_eq!(TokenMillError::from_transaction_error(&err), None);
+    assert_eq!(describe_transaction_error(&err), err.to_string());
+    // Codes of the program that aren't listed keep their raw form
+    let err = TransactionError::InstructionError(2, InstructionError::Custom(6000));
+    assert_eq!(TokenMillError::from_transaction_error(&err), None);
+    assert_eq!(describe_transaction_error(&err), err.to_string());
+
+    let logs = [
+        format!("Program {} invoke [1]", TOKENMILL_PROGRAM),
+        "Program log: AnchorError occurred. Error Code: SlippageExceeded. Error Number: 6001.".to_string(),
+        format!("Program {} failed: custom program error: 0x1771", TOKENMILL_PROGRAM),
+    ];
+    assert_eq!(TokenMillError::from_logs(&logs), Some(TokenMillError::SlippageExceeded));
+    // Failures of the programs TokenMill calls into are theirs
+    let logs = [format!("Program {} failed: custom program error: 0x1", TOKENMILL_PROGRAM)];
+    assert_eq!(TokenMillError::from_logs(&logs), None);
+}
+
+fn failure_logs(anchor_log: &str, code: u32) -> Vec<String> {
+    vec![
+        format!("Program {} invoke [1]", TOKENMILL_PROGRAM),
+        "Program log: Instruction: SwapExactIn".to_string(),
+        anchor_log.to_string(),
+        format!("Program {} consumed 24018 of 200000 compute units", TOKENMILL_PROGRAM),
+        format!("Program {} failed: custom program error: {:#x}", TOKENMILL_PROGRAM, code),
+    ]
+}
+
+#[test]
+fn program_errors_are_named_from_the_anchor_logs() {
+    let cases = [
+        (
+            "Program log: AnchorError occurred. Error Code: SlippageExceeded. Error Number: 6001. Error Message: Slippage exceeded.",
+            6001,
+            "SlippageExceeded",
+            "Slippage exceeded",
+        ),
+        (
+            "Program log: AnchorError thrown in programs/token-mill-v2/src/instructions/swap.rs:87. Error Code: InvalidAmount. Error Number: 6000. Error Message: Invalid amount.",
+            6000,
+            "InvalidAmount",
+            "Invalid amount",
+        ),
+        (
+            "Program log: AnchorError caused by account: market. Error Code: ConstraintHasOne. Error Number: 2001. Error Message: A has one constraint was violated.",
+            2001,
+            "ConstraintHasOne",
+            "A has one constraint was violated",
+        ),
+    ];
+
+    for (log, code, name, message) in cases {
+        let err = ProgramError::from_logs(&failure_logs(log, code)).unwrap();
+        assert_eq!(err, ProgramError { code, name: name.to_string(), message: message.to_string() });
+        assert_eq!(err.to_string(), format!("{}: {}", name, message));
+    }
+
+    // The Anchor error has to be the one the program failed with
+    let log = "Program log: AnchorError occurred. Error Code: InvalidAmount. Error Number: 6000. Error Message: Invalid amount.";
+    assert_eq!(ProgramError::from_logs(&failure_logs(log, 6001)), None);
+    let mut logs = failure_logs(log, 6000);
+    logs.pop();
+    assert_eq!(ProgramError::from_logs(&logs), None);
+    assert_eq!(ProgramError::from_logs(&failure_logs("Program log: custom failure", 6000)), None);
+}
@@ -0,0 +1,88 @@
+use solana_sdk::pubkey::Pubkey;
+
+use super::mid_curve_market;
+use crate::fees::{
+    build_claim_creator_fees_instruction, build_claim_transactions, claimable_creator_fees, CreatorFees, FeeReserve,
+    CLAIM_CREATOR_FEES_DISCRIMINATOR,
+};
+use crate::market::TOKENMILL_PROGRAM;
+use crate::supply::quote_reserve;
+use crate::swap::SwapOptions;
+
+#[test]
+fn creator_fees_are_the_reserve_above_what_the_curve_owes() {
+    let market = mid_curve_market();
+    let curve_reserve = quote_reserve(&market.settings, market.sqrt_price_x96).unwrap();
+
+    assert_eq!(claimable_creator_fees(&market, curve_reserve + 1_234, None).unwrap(), 1_234);
+    assert_eq!(claimable_creator_fees(&market, curve_reserve - 1, None).unwrap(), 0);
+}
+
+#[test]
+fn creator_fees_of_markets_with_a_fee_reserve_are_its_balance() {
+    let mut market = mid_curve_market();
+    market.fee_reserve = Some(Pubkey::new_unique());
+    market.fee_reserve_last_update = 1_700_000_000;
+    let curve_reserve = quote_reserve(&market.settings, market.sqrt_price_x96).unwrap();
+
+    assert_eq!(claimable_creator_fees(&market, curve_reserve + 1_234, Some(5_678)).unwrap(), 5_678);
+    assert_eq!(claimable_creator_fees(&market, curve_reserve, Some(0)).unwrap(), 0);
+    let err = claimable_creator_fees(&market, curve_reserve + 1_234, None).unwrap_err();
+    assert!(err.to_string().starts_with("FeeReserveMissing"));
+}
+
+#[test]
+fn creator_fee_claims_are_packed_into_full_transactions() {
+    let creator = Pubkey::new_unique();
+    let token_mint1 = spl_token::native_mint::id();
+    // Every other market pays its fees from a fee reserve
+    let claims = (0..40)
+        .map(|index| {
+            let mut market = mid_curve_market();
+            market.token_mint1 = token_mint1;
+            market.fee_reserve = (index % 2 == 1).then(Pubkey::new_unique);
+            CreatorFees {
+                market_address: Pubkey::new_unique(),
+                token_program1: spl_token::id(),
+                claimable: 1,
+                fee_reserve: market.fee_reserve.map(|address| FeeReserve { address, balance: 1, last_update: 0 }),
+                market,
+            }
+        })
+        .collect::<Vec<_>>();
+    let program_id = Pubkey::from_str_const(TOKENMILL_PROGRAM);
+    let fee_reserve_account = |fees: &CreatorFees| {
+        let account = build_claim_creator_fees_instruction(fees, &creator).accounts[4].clone();
+        (account.pubkey, account.is_writable)
+    };
+    assert_eq!(fee_reserve_account(&claims[0]), (program_id, false));
+    assert_eq!(fee_reserve_account(&claims[1]), (claims[1].market.fee_reserve.unwrap(), true));
+
+    let transactions = build_claim_transactions(&claims, &creator, &SwapOptions::default()).unwrap();
+    assert!(transactions.len() > 1 && transactions.len() < claims.len());
+
+    let mut claimed = vec![];
+    for transaction in transactions.iter() {
+        assert!(bincode::serialized_size(transaction).unwrap() <= solana_sdk::packet::PACKET_DATA_SIZE as u64);
+
+        let message = &transaction.message;
+        let instructions = message.instructions.iter().map(|instruction| {
+            (message.account_keys[instruction.program_id_index as usize], instruction)
+        });
+        // The token account is created once, before the claims
+        let programs = instructions.clone().map(|(program_id, _)| program_id).collect::<Vec<_>>();
+        assert_eq!(programs[0], spl_associated_token_account::id());
+        assert!(programs[1..].iter().all(|id| *id == program_id));
+
+        for (_, instruction) in instructions.skip(1) {
+            assert_eq!(instruction.data, CLAIM_CREATOR_FEES_DISCRIMINATOR);
+            let account = |position: usize| message.account_keys[instruction.accounts[position] as usize];
+            claimed.push((account(1), account(4)));
+        }
+    }
+    let expected = claims
+        .iter()
+        .map(|fees| (fees.market_address, fees.market.fee_reserve.unwrap_or(program_id)))
+        .collect::<Vec<_>>();
+    assert_eq!(claimed, expected);
+}
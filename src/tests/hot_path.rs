@@ -0,0 +1,109 @@
+use proptest::prelude::*;
+use ruint::aliases::U256;
+
+use super::{arb_market, with_sqrt_price};
+use super::reference;
+use crate::error::SwapMathError;
+use crate::math::{mul_div, mul_div_round_up};
+use crate::quote::{quote, PreparedMarket};
+use crate::swap_math::{
+    get_amount_0, get_amount_1, get_delta_amounts, get_next_sqrt_ratio_from_amount_0,
+    get_next_sqrt_ratio_from_amount_1,
+};
+
+prop_compose! {
+    // Values of any magnitude, so that products land on both sides of 2^256
+    fn arb_u256()(limbs in any::<[u64; 4]>(), shift in 0usize..256) -> U256 {
+        U256::from_limbs(limbs) >> shift
+    }
+}
+
+proptest! {
+    #[test]
+    fn mul_div_matches_the_reference(x in arb_u256(), y in arb_u256(), denominator in arb_u256()) {
+        prop_assert_eq!(mul_div(x, y, denominator), reference::math::mul_div(x, y, denominator));
+        prop_assert_eq!(
+            mul_div_round_up(x, y, denominator),
+            reference::math::mul_div_round_up(x, y, denominator)
+        );
+    }
+
+    #[test]
+    fn swap_math_matches_the_reference(
+        sqrt_price in any::<u128>(),
+        target_sqrt_price in any::<u128>(),
+        liquidity in 1..=u128::MAX,
+        delta_amount in any::<i64>(),
+        fee in 0u32..1_000_000,
+    ) {
+        prop_assume!(sqrt_price > 0 && target_sqrt_price > 0);
+
+        for adding in [false, true] {
+            prop_assert_eq!(
+                get_amount_0(sqrt_price, target_sqrt_price, liquidity, adding),
+                reference::swap_math::get_amount_0(sqrt_price, target_sqrt_price, liquidity, adding)
+            );
+            prop_assert_eq!(
+                get_amount_1(sqrt_price, target_sqrt_price, liquidity, adding),
+                reference::swap_math::get_amount_1(sqrt_price, target_sqrt_price, liquidity, adding)
+            );
+        }
+        prop_assert_eq!(
+            get_next_sqrt_ratio_from_amount_0(sqrt_price, liquidity, delta_amount),
+            reference::swap_math::get_next_sqrt_ratio_from_amount_0(sqrt_price, liquidity, delta_amount)
+        );
+        prop_assert_eq!(
+            get_next_sqrt_ratio_from_amount_1(sqrt_price, liquidity, delta_amount),
+            reference::swap_math::get_next_sqrt_ratio_from_amount_1(sqrt_price, liquidity, delta_amount)
+        );
+        prop_assert_eq!(
+            get_delta_amounts(sqrt_price, target_sqrt_price, liquidity, delta_amount, fee),
+            reference::swap_math::get_delta_amounts(sqrt_price, target_sqrt_price, liquidity, delta_amount, fee)
+        );
+    }
+
+    #[test]
+    fn quote_matches_the_reference(
+        market in arb_market(),
+        zero_for_one in any::<bool>(),
+        delta_amount in prop_oneof![any::<i64>(), -(1i64 << 50)..(1i64 << 50)],
+    ) {
+        let sqrt_price_limit = if zero_for_one { market.settings.sqrt_price_a_x96 } else { u128::MAX };
+
+        prop_assert_eq!(
+            quote(&market, zero_for_one, delta_amount, sqrt_price_limit).map(Into::into),
+            reference::quote::quote(&market, zero_for_one, delta_amount, sqrt_price_limit)
+        );
+    }
+
+    // A prepared market is reused across swaps, only the price moves
+    #[test]
+    fn prepared_market_matches_the_reference_along_a_route(
+        market in arb_market(),
+        swaps in prop::collection::vec((any::<bool>(), -(1i64 << 50)..(1i64 << 50)), 1..20),
+    ) {
+        let prepared = PreparedMarket::new(&market.settings);
+        let mut market = market;
+
+        for (zero_for_one, delta_amount) in swaps {
+            let sqrt_price_limit = if zero_for_one { market.settings.sqrt_price_a_x96 } else { u128::MAX };
+            let expected = reference::quote::quote(&market, zero_for_one, delta_amount, sqrt_price_limit);
+
+            prop_assert_eq!(
+                prepared.quote(market.sqrt_price_x96, zero_for_one, delta_amount, sqrt_price_limit).map(Into::into),
+                expected.clone()
+            );
+            if let Ok(expected) = expected {
+                market = with_sqrt_price(&market, expected.next_sqrt_price);
+            }
+        }
+    }
+}
+
+// The previous implementation divided by the liquidity unchecked
+#[test]
+fn next_sqrt_ratio_from_amount_1_fails_without_liquidity() {
+    for amount_1 in [0, 1, i64::MAX] {
+        assert_eq!(get_next_sqrt_ratio_from_amount_1(1 << 96, 0, amount_1), Err(SwapMathError::DivisionByZero));
+    }
+}
@@ -0,0 +1,114 @@
+mod authority;
+mod backtest;
+mod bundle;
+mod conformance;
+mod create_market;
+mod depth;
+mod design;
+mod error;
+mod fees;
+mod hot_path;
+mod portfolio;
+mod quote;
+mod reference;
+mod retry;
+mod route;
+#[cfg(feature = "serde")]
+mod serialization;
+mod sizing;
+mod state;
+mod supply;
+mod swap_math;
+mod sweep;
+mod target;
+mod tracker;
+
+use proptest::prelude::*;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::market::{Market, MarketSettings};
+
+pub fn market_with(settings: MarketSettings, sqrt_price_x96: u128) -> Market {
+    Market {
+        discriminator: [0; 8],
+        config: Pubkey::default(),
+        creator: Pubkey::default(),
+        swap_authority: None,
+        token_mint0: Pubkey::new_unique(),
+        token_mint1: Pubkey::new_unique(),
+        reserve0: Pubkey::default(),
+        reserve1: Pubkey::default(),
+        fee_reserve: None,
+        fee_reserve_last_update: 0,
+        settings,
+        sqrt_price_x96,
+        bump: [0],
+    }
+}
+
+// Curve of a launched token, phase B starting at three times the start price
+pub fn curve_settings() -> MarketSettings {
+    MarketSettings {
+        max_supply: 1_000_000_000_000_000,
+        sqrt_price_a_x96: 79_228_162_514_264_337_593_543_950,
+        sqrt_price_b_x96: 237_684_487_542_793_012_780_631_850,
+        liquidity_a: 1_200_000_000_000,
+        liquidity_b: 4_000_000_000_000,
+        fee: 10_000,
+    }
+}
+
+// Halfway through phase A of `curve_settings`
+pub fn mid_curve_market() -> Market {
+    let settings = curve_settings();
+    let sqrt_price_x96 = 2 * settings.sqrt_price_a_x96;
+
+    market_with(settings, sqrt_price_x96)
+}
+
+pub fn restricted_market(swap_authority: &Pubkey) -> Market {
+    let mut market = mid_curve_market();
+    market.swap_authority = Some(*swap_authority);
+    market
+}
+
+prop_compose! {
+    pub fn arb_settings()(
+        sqrt_price_a_shift in 80u32..100,
+        sqrt_price_a_extra in any::<u64>(),
+        phase_b_per_mille in 10u128..10_000,
+        liquidity_a_shift in 36u32..64,
+        liquidity_a_extra in any::<u16>(),
+        liquidity_b_shift in 36u32..64,
+        liquidity_b_extra in any::<u16>(),
+        fee in 0u32..100_000,
+    ) -> MarketSettings {
+        let sqrt_price_a_x96 = (1u128 << sqrt_price_a_shift) + u128::from(sqrt_price_a_extra);
+
+        // Liquidities are scaled on the price so that the supply of each phase roughly fits in a u64
+        MarketSettings {
+            max_supply: u64::MAX,
+            sqrt_price_a_x96,
+            sqrt_price_b_x96: sqrt_price_a_x96 + sqrt_price_a_x96 * phase_b_per_mille / 1_000,
+            liquidity_a: (sqrt_price_a_x96 >> liquidity_a_shift) + u128::from(liquidity_a_extra),
+            liquidity_b: (sqrt_price_a_x96 >> liquidity_b_shift) + u128::from(liquidity_b_extra),
+            fee,
+        }
+    }
+}
+
+prop_compose! {
+    // Current price anywhere between the start of the curve and twice as far past the phase boundary
+    pub fn arb_market()(settings in arb_settings(), position in 0u128..=2_000) -> Market {
+        let span = 2 * (settings.sqrt_price_b_x96 - settings.sqrt_price_a_x96);
+        let sqrt_price_x96 = settings.sqrt_price_a_x96 + span * position / 2_000;
+
+        market_with(settings, sqrt_price_x96)
+    }
+}
+
+pub fn with_sqrt_price(market: &Market, sqrt_price_x96: u128) -> Market {
+    let mut market = market.clone();
+    market.sqrt_price_x96 = sqrt_price_x96;
+    market
+}
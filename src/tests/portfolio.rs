@@ -0,0 +1,84 @@
+use serde_json::json;
+use solana_account_decoder::{parse_account_data::ParsedAccount, UiAccount, UiAccountData};
+use solana_client::rpc_response::RpcKeyedAccount;
+use solana_sdk::pubkey::Pubkey;
+
+use super::mid_curve_market;
+use crate::market::Market;
+use crate::portfolio::{parse_token_holding, portfolio_totals, value_position, PortfolioPosition, TokenHolding};
+
+#[test]
+fn positions_are_valued_below_spot_when_liquidated() {
+    let market = mid_curve_market();
+
+    let small = value_position(&market, 10_000_000_000).unwrap();
+    let large = value_position(&market, 100_000_000_000_000).unwrap();
+    assert!(large.liquidation_value < large.spot_value);
+    assert!(large.price_impact_bps > small.price_impact_bps && small.price_impact_bps > 0.0);
+    assert_eq!(value_position(&market, 0).unwrap().liquidation_value, 0);
+
+    let position = |market: &Market, amount| {
+        let holding = TokenHolding {
+            address: Pubkey::new_unique(),
+            mint: market.token_mint0,
+            token_program: spl_token_2022::id(),
+            amount,
+            lamports: 2_039_280,
+        };
+        PortfolioPosition::new(holding, Some((Pubkey::new_unique(), market.clone())))
+    };
+    // Too large to be sold in a single swap
+    let unsellable = position(&market, u64::MAX);
+    assert!(unsellable.valuation.is_none() && unsellable.valuation_error.is_some());
+    assert!(unsellable.to_json()["valuation_error"].is_string());
+
+    let other_market = mid_curve_market();
+    let totals = portfolio_totals(&[
+        position(&market, 10_000_000_000),
+        position(&other_market, 1_000),
+        unsellable,
+        position(&market, 100_000_000_000_000),
+    ]);
+    assert_eq!(totals.len(), 2);
+    assert_eq!(totals[0].1.liquidation_value, small.liquidation_value + large.liquidation_value);
+}
+
+#[test]
+fn token_holdings_are_read_from_parsed_accounts() {
+    let (address, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let keyed_account = |data| RpcKeyedAccount {
+        pubkey: address.to_string(),
+        account: UiAccount {
+            lamports: 2_039_280,
+            data,
+            owner: spl_token_2022::id().to_string(),
+            executable: false,
+            rent_epoch: 0,
+            space: Some(170),
+        },
+    };
+    let parsed = ParsedAccount {
+        program: "spl-token-2022".to_string(),
+        parsed: json!({
+            "type": "account",
+            "info": {
+                "mint": mint.to_string(),
+                "owner": Pubkey::new_unique().to_string(),
+                "tokenAmount": { "amount": "18446744073709551615", "decimals": 6, "uiAmountString": "18446744073709.551615" },
+            },
+        }),
+        space: 170,
+    };
+
+    let holding = parse_token_holding(&keyed_account(UiAccountData::Json(parsed.clone())), &spl_token_2022::id()).unwrap();
+    assert_eq!(
+        holding,
+        TokenHolding { address, mint, token_program: spl_token_2022::id(), amount: u64::MAX, lamports: 2_039_280 }
+    );
+
+    let mut without_amount = parsed;
+    without_amount.parsed["info"]["tokenAmount"] = json!(null);
+    assert!(parse_token_holding(&keyed_account(UiAccountData::Json(without_amount)), &spl_token::id()).is_err());
+    let binary = UiAccountData::LegacyBinary(String::new());
+    assert!(parse_token_holding(&keyed_account(binary), &spl_token::id()).is_err());
+}
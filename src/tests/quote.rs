@@ -0,0 +1,227 @@
+use proptest::prelude::*;
+
+use super::{arb_market, arb_settings, market_with, with_sqrt_price};
+use crate::market::Market;
+use crate::quote::{quote, quote_with_legs, Phase, Quote};
+use crate::swap_math::{get_amount_0, get_amount_1};
+
+prop_compose! {
+    fn arb_phase_a_market()(settings in arb_settings(), position in 0u128..1_000) -> Market {
+        let span = settings.sqrt_price_b_x96 - settings.sqrt_price_a_x96;
+        let sqrt_price_x96 = settings.sqrt_price_a_x96 + span * position / 1_000;
+
+        market_with(settings, sqrt_price_x96)
+    }
+}
+
+proptest! {
+    #[test]
+    fn buying_then_selling_never_returns_more_than_paid(
+        market in arb_market(),
+        amount in 1u64..(1u64 << 62),
+    ) {
+        let sqrt_price_a = market.settings.sqrt_price_a_x96;
+
+        let buy = quote(&market, false, amount as i64, u128::MAX);
+        prop_assume!(buy.is_ok());
+        let buy = buy.unwrap();
+        prop_assume!(i64::try_from(buy.amount_out).is_ok());
+        let after_buy = with_sqrt_price(&market, buy.next_sqrt_price);
+
+        if let Ok(sell) = quote(&after_buy, true, buy.amount_out as i64, sqrt_price_a) {
+            prop_assert!(sell.amount_out <= buy.amount_in);
+        }
+
+        // Getting back exactly what was paid costs at least what was bought, if reachable at all
+        if let Ok(sell) = quote(&after_buy, true, -(buy.amount_in as i64), sqrt_price_a) {
+            prop_assert!(sell.amount_out < buy.amount_in || sell.amount_in >= buy.amount_out);
+        }
+    }
+
+    #[test]
+    fn selling_then_buying_never_returns_more_than_sold(
+        market in arb_market(),
+        amount in 1u64..(1u64 << 62),
+    ) {
+        let sell = quote(&market, true, amount as i64, market.settings.sqrt_price_a_x96);
+        prop_assume!(sell.is_ok());
+        let sell = sell.unwrap();
+        prop_assume!(i64::try_from(sell.amount_out).is_ok());
+        let after_sell = with_sqrt_price(&market, sell.next_sqrt_price);
+
+        // The fee is paid in token 0 but swapped to token 1, so only `amount_out` goes back to the user
+        if let Ok(buy) = quote(&after_sell, false, sell.amount_out as i64, u128::MAX) {
+            prop_assert!(buy.amount_out <= sell.amount_in);
+        }
+    }
+
+    #[test]
+    fn amounts_are_monotonic_in_size(
+        market in arb_market(),
+        zero_for_one in any::<bool>(),
+        exact_in in any::<bool>(),
+        first in 1u64..(1u64 << 62),
+        second in 1u64..(1u64 << 62),
+    ) {
+        let (small, large) = (first.min(second) as i64, first.max(second) as i64);
+        let (small, large) = if exact_in { (small, large) } else { (-small, -large) };
+        let sqrt_price_limit = if zero_for_one { market.settings.sqrt_price_a_x96 } else { u128::MAX };
+
+        if let (Ok(small), Ok(large)) = (
+            quote(&market, zero_for_one, small, sqrt_price_limit),
+            quote(&market, zero_for_one, large, sqrt_price_limit),
+        ) {
+            prop_assert!(small.amount_in <= large.amount_in);
+            prop_assert!(small.amount_out <= large.amount_out);
+            prop_assert!(small.fee_amount_token_in <= large.fee_amount_token_in);
+        }
+    }
+
+    #[test]
+    fn crossing_the_phase_boundary_is_continuous(
+        market in arb_phase_a_market(),
+        extra in 1u64..(1u64 << 40),
+    ) {
+        let settings = &market.settings;
+
+        // Smallest buy taking the price exactly to the phase boundary
+        let to_boundary = get_amount_1(market.sqrt_price_x96, settings.sqrt_price_b_x96, settings.liquidity_a, true);
+        prop_assume!(to_boundary.is_ok());
+        let to_boundary = (to_boundary.unwrap() * 1_000_000).div_ceil(1_000_000 - u128::from(settings.fee));
+        prop_assume!(to_boundary > 0 && to_boundary < 1 << 61);
+        let to_boundary = to_boundary as i64;
+
+        let single = quote(&market, false, to_boundary + extra as i64, u128::MAX);
+        let first = quote(&market, false, to_boundary, u128::MAX);
+        prop_assume!(single.is_ok() && first.is_ok());
+        let (single, first) = (single.unwrap(), first.unwrap());
+        prop_assert!(first.next_sqrt_price >= settings.sqrt_price_b_x96);
+
+        let second = quote(&with_sqrt_price(&market, first.next_sqrt_price), false, extra as i64, u128::MAX);
+        prop_assume!(second.is_ok());
+        let second = second.unwrap();
+
+        // Splitting at the boundary only differs by rounding, worth at most one sqrt price unit per leg
+        let split_amount_out = first.amount_out + second.amount_out;
+        let tolerance = 2 + 2 * get_amount_0(
+            single.next_sqrt_price,
+            single.next_sqrt_price + 1,
+            settings.liquidity_b,
+            true,
+        ).unwrap_or(0) as u64;
+
+        prop_assert_eq!(single.amount_in, first.amount_in + second.amount_in);
+        prop_assert!(split_amount_out <= single.amount_out + tolerance);
+        prop_assert!(single.amount_out <= split_amount_out + tolerance);
+    }
+
+    #[test]
+    fn quote_legs_add_up_to_the_totals(
+        market in arb_market(),
+        zero_for_one in any::<bool>(),
+        exact_in in any::<bool>(),
+        amount in 1i64..(1i64 << 62),
+    ) {
+        let settings = &market.settings;
+        let sqrt_price_limit = if zero_for_one { settings.sqrt_price_a_x96 } else { u128::MAX };
+        let delta_amount = if exact_in { amount } else { -amount };
+
+        // Collecting the legs doesn't change the quote
+        let expected = quote(&market, zero_for_one, delta_amount, sqrt_price_limit);
+        let quote = quote_with_legs(&market, zero_for_one, delta_amount, sqrt_price_limit);
+        prop_assert_eq!(quote.clone().map(|quote| Quote { legs: vec![], ..quote }), expected);
+
+        if let Ok(quote) = quote {
+            prop_assert!(!quote.legs.is_empty() && quote.legs.len() <= 2);
+            prop_assert_eq!(quote.legs[0].sqrt_price_start, market.sqrt_price_x96);
+            for legs in quote.legs.windows(2) {
+                prop_assert_eq!(legs[0].sqrt_price_end, legs[1].sqrt_price_start);
+                prop_assert_eq!(legs[0].sqrt_price_end, settings.sqrt_price_b_x96);
+                prop_assert_ne!(legs[0].phase, legs[1].phase);
+            }
+            prop_assert_eq!(quote.crossed_phase_boundary, quote.legs.len() == 2);
+
+            let last = quote.legs.last().unwrap();
+            let legs_amount_in = quote.legs.iter().map(|leg| leg.amount_in + leg.fee_amount).sum::<u64>();
+            prop_assert_eq!(legs_amount_in, quote.amount_in);
+            prop_assert_eq!(quote.legs.iter().map(|leg| leg.amount_out).sum::<u64>(), quote.amount_out);
+            prop_assert_eq!(quote.legs.iter().map(|leg| leg.fee_amount).sum::<u64>(), quote.fee_amount_token_in);
+            let filled = if exact_in { quote.amount_in } else { quote.amount_out };
+            prop_assert_eq!(
+                quote.hit_sqrt_price_limit,
+                last.sqrt_price_end == sqrt_price_limit && filled < delta_amount.unsigned_abs()
+            );
+            if !zero_for_one {
+                prop_assert_eq!(last.sqrt_price_end, quote.next_sqrt_price);
+            }
+        }
+    }
+
+    // Swapping exactly what it takes to reach the limit fills the swap there, which isn't stopping at the limit
+    #[test]
+    fn swaps_filled_at_the_limit_did_not_hit_it(
+        market in arb_market(),
+        zero_for_one in any::<bool>(),
+        exact_in in any::<bool>(),
+        step in 1u128..(1u128 << 96),
+    ) {
+        let settings = &market.settings;
+        let sqrt_price_limit = if zero_for_one {
+            market.sqrt_price_x96.saturating_sub(step).max(settings.sqrt_price_a_x96)
+        } else {
+            market.sqrt_price_x96.saturating_add(step)
+        };
+        let delta_amount = if exact_in { i64::MAX } else { i64::MIN };
+
+        let to_limit = quote(&market, zero_for_one, delta_amount, sqrt_price_limit);
+        prop_assume!(to_limit.is_ok());
+        let to_limit = to_limit.unwrap();
+        prop_assume!(to_limit.hit_sqrt_price_limit);
+        let amount = if exact_in { to_limit.amount_in } else { to_limit.amount_out };
+        prop_assume!(amount > 0);
+        let delta_amount = if exact_in { amount as i64 } else { -(amount as i64) };
+
+        let quote = quote(&market, zero_for_one, delta_amount, sqrt_price_limit).unwrap();
+        let filled = if exact_in { quote.amount_in } else { quote.amount_out };
+        prop_assert_eq!(filled, amount);
+        prop_assert!(!quote.hit_sqrt_price_limit);
+        if !exact_in {
+            prop_assert_eq!(quote.next_sqrt_price, to_limit.next_sqrt_price);
+        }
+    }
+
+    // A market on `sqrt_price_b_x96` is in phase B, but a sell from there has nothing left to do in it
+    #[test]
+    fn sells_from_the_phase_boundary_only_trade_in_phase_a(
+        settings in arb_settings(),
+        exact_in in any::<bool>(),
+        amount in 1i64..(1i64 << 62),
+    ) {
+        let (sqrt_price_a_x96, sqrt_price_b_x96) = (settings.sqrt_price_a_x96, settings.sqrt_price_b_x96);
+        let market = market_with(settings, sqrt_price_b_x96);
+        let delta_amount = if exact_in { amount } else { -amount };
+
+        if let Ok(quote) = quote_with_legs(&market, true, delta_amount, sqrt_price_a_x96) {
+            prop_assert!(!quote.crossed_phase_boundary);
+            prop_assert_eq!(quote.legs.len(), 1);
+            prop_assert_eq!(quote.legs[0].phase, Phase::A);
+            prop_assert_eq!(quote.legs[0].sqrt_price_start, sqrt_price_b_x96);
+        }
+    }
+
+    #[test]
+    fn quote_never_panics(
+        market in arb_market(),
+        zero_for_one in any::<bool>(),
+        delta_amount in prop_oneof![any::<i64>(), Just(i64::MIN), Just(i64::MAX), Just(-1i64), Just(1i64)],
+        sqrt_price_limit in prop_oneof![Just(None), any::<u128>().prop_map(Some)],
+    ) {
+        let sqrt_price_limit = sqrt_price_limit.unwrap_or(if zero_for_one {
+            market.settings.sqrt_price_a_x96
+        } else {
+            u128::MAX
+        });
+
+        let _ = quote(&market, zero_for_one, delta_amount, sqrt_price_limit);
+    }
+}
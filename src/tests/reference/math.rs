@@ -0,0 +1,41 @@
+use ruint::aliases::{U256, U512};
+
+use crate::error::SwapMathError;
+
+pub fn mul_div(x: U256, y: U256, denominator: U256) -> Result<u128, SwapMathError> {
+    if denominator.is_zero() {
+        return Err(SwapMathError::DivisionByZero);
+    }
+
+    let x = U512::from(x);
+    let y = U512::from(y);
+    let denominator = U512::from(denominator);
+
+    let prod = x.wrapping_mul(y);
+
+    let (quotient, _) = prod.div_rem(denominator);
+
+    quotient
+        .try_into()
+        .map_err(|_| SwapMathError::AmountOverflow)
+}
+
+pub fn mul_div_round_up(x: U256, y: U256, denominator: U256) -> Result<u128, SwapMathError> {
+    if denominator.is_zero() {
+        return Err(SwapMathError::DivisionByZero);
+    }
+
+    let prod = U512::from(x).wrapping_mul(U512::from(y));
+
+    // Rounds up whenever the product itself isn't a multiple of the denominator
+    let (quotient, remainder) = prod.div_rem(U512::from(denominator));
+    let quotient = if remainder.is_zero() {
+        quotient
+    } else {
+        quotient + U512::from(1)
+    };
+
+    quotient
+        .try_into()
+        .map_err(|_| SwapMathError::AmountOverflow)
+}
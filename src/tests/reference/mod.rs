@@ -0,0 +1,4 @@
+// The quoting code as it was before the hot path rework, which must keep returning the exact same results
+pub mod math;
+pub mod quote;
+pub mod swap_math;
@@ -0,0 +1,38 @@
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+use super::mid_curve_market;
+use crate::retry::{classify_transaction_error, worst_price, FailureKind, RetryPolicy};
+use crate::swap::SwapParameters;
+
+#[test]
+fn swap_failures_are_classified_by_whether_a_retry_can_land() {
+    let cases = [
+        (TransactionError::InstructionError(3, InstructionError::Custom(0x1771)), FailureKind::Slippage),
+        (TransactionError::BlockhashNotFound, FailureKind::BlockhashNotFound),
+        (TransactionError::AccountInUse, FailureKind::AccountInUse),
+        (TransactionError::InsufficientFundsForFee, FailureKind::InsufficientFunds),
+        (TransactionError::InstructionError(2, InstructionError::Custom(1)), FailureKind::InsufficientFunds),
+        (TransactionError::InstructionError(3, InstructionError::Custom(6000)), FailureKind::Other),
+    ];
+    for (err, kind) in cases {
+        assert_eq!(classify_transaction_error(&err), kind);
+    }
+    assert!(FailureKind::Slippage.is_retryable() && !FailureKind::InsufficientFunds.is_retryable());
+}
+
+#[test]
+fn requotes_past_the_price_limit_are_refused() {
+    let market = mid_curve_market();
+    let policy = |price_limit| RetryPolicy { max_attempts: 3, slippage_bps: 100, price_limit };
+
+    let buy = SwapParameters::BuyExactIn(1_000_000, 0);
+    let price = worst_price(&policy(None).requote(&market, &buy).unwrap().parameters).unwrap();
+    assert!(policy(Some(price)).requote(&market, &buy).is_ok());
+    assert!(policy(Some(price * 0.99)).requote(&market, &buy).unwrap_err().to_string().starts_with("PriceLimitExceeded"));
+    assert_eq!(worst_price(&buy), None);
+
+    let sell = SwapParameters::SellExactOut(1_000_000, 0);
+    let price = worst_price(&policy(None).requote(&market, &sell).unwrap().parameters).unwrap();
+    assert!(policy(Some(price)).requote(&market, &sell).is_ok());
+    assert!(policy(Some(price * 1.01)).requote(&market, &sell).is_err());
+}
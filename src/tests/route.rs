@@ -0,0 +1,122 @@
+use solana_sdk::{pubkey::Pubkey, system_program};
+
+use super::market_with;
+use crate::market::{MarketSettings, TOKENMILL_PROGRAM};
+use crate::route::{best_route, build_route_instructions, find_routes, quote_route, RouteMarket};
+use crate::swap::{quote_swap, SwapAccounts, SwapOptions, SwapParameters};
+
+fn settings() -> MarketSettings {
+    MarketSettings {
+        max_supply: 1_000_000_000_000_000,
+        sqrt_price_a_x96: 79_228_162_514_264_337_593_543_950,
+        sqrt_price_b_x96: 237_684_487_542_793_012_780_631_850,
+        liquidity_a: 1_200_000_000_000,
+        liquidity_b: 4_000_000_000_000,
+        fee: 10_000,
+    }
+}
+
+// Launched token quoted in wSOL, halfway through its curve
+fn sol_market() -> RouteMarket {
+    let mut market = market_with(settings(), settings().sqrt_price_b_x96);
+    market.token_mint1 = spl_token::native_mint::id();
+
+    RouteMarket {
+        accounts: SwapAccounts::new(&Pubkey::new_unique(), &market, &spl_token_2022::id(), &spl_token::id()),
+        market,
+    }
+}
+
+#[test]
+fn routes_go_through_the_shared_quote_token() {
+    let (a, b, restricted) = (sol_market(), sol_market(), sol_market());
+    let mut restricted_market = restricted.clone();
+    restricted_market.market.swap_authority = Some(Pubkey::new_unique());
+    let markets = [a.clone(), b.clone(), restricted_market];
+    let (mint_a, mint_b) = (a.market.token_mint0, b.market.token_mint0);
+
+    let routes = find_routes(&markets, &mint_a, &mint_b, 2);
+    assert_eq!(routes.len(), 1);
+    let hops = &routes[0];
+    assert_eq!(hops.len(), 2);
+    assert_eq!((hops[0].mint_in(), hops[0].mint_out()), (mint_a, spl_token::native_mint::id()));
+    assert_eq!((hops[1].mint_in(), hops[1].mint_out()), (spl_token::native_mint::id(), mint_b));
+
+    assert!(find_routes(&markets, &mint_a, &mint_b, 1).is_empty());
+    assert_eq!(find_routes(&markets, &mint_a, &spl_token::native_mint::id(), 2).len(), 1);
+    // The restricted market needs a co-signature
+    assert!(find_routes(&markets, &mint_a, &restricted.market.token_mint0, 2).is_empty());
+}
+
+#[test]
+fn each_hop_spends_what_the_previous_one_guarantees() {
+    let (a, b) = (sol_market(), sol_market());
+    let markets = [a.clone(), b.clone()];
+    let amount_in = 1_000_000_000_000;
+
+    let route = best_route(&markets, &a.market.token_mint0, &b.market.token_mint0, amount_in, 100, 2).unwrap();
+    let (sell, buy) = (&route.swaps[0], &route.swaps[1]);
+
+    assert_eq!(sell.parameters, SwapParameters::SellExactIn(amount_in, sell.parameters.threshold()));
+    assert_eq!(buy.parameters.amount(), sell.parameters.threshold());
+    assert_eq!(route.min_amount_out, buy.parameters.threshold());
+    assert!(buy.quote.amount_out >= route.min_amount_out);
+
+    // Slippage is taken once, on the expected output of the whole route
+    let expected = quote_swap(&a.market, &SwapParameters::SellExactIn(amount_in, 0)).unwrap().amount_out;
+    let expected = quote_swap(&b.market, &SwapParameters::BuyExactIn(expected, 0)).unwrap().amount_out;
+    assert_eq!(route.min_amount_out, expected * 9_900 / 10_000);
+    // The sell only has to return what the buy needs to meet it
+    let short = SwapParameters::BuyExactIn(sell.parameters.threshold() - 1, 0);
+    assert!(quote_swap(&b.market, &short).unwrap().amount_out < route.min_amount_out);
+    assert!(sell.parameters.threshold() < sell.quote.amount_out);
+
+    // Without slippage, the buy needs at most the whole output of the sell
+    let route = quote_route(&route.hops, amount_in, 0).unwrap();
+    assert_eq!(route.min_amount_out, expected);
+    assert!(route.swaps[1].parameters.amount() <= route.swaps[0].quote.amount_out);
+}
+
+#[test]
+fn routes_report_what_their_swaps_deliver() {
+    let (a, b) = (sol_market(), sol_market());
+    let markets = [a.clone(), b.clone()];
+
+    let route = best_route(&markets, &a.market.token_mint0, &b.market.token_mint0, 1_000_000_000_000, 100, 2).unwrap();
+    let (sell, buy) = (&route.swaps[0], &route.swaps[1]);
+
+    // The buy only spends its share of the sell output, so the route returns less than the hops chained on
+    // their whole outputs, the rest of the wSOL staying in the user account
+    assert_eq!(route.amount_out, buy.quote.amount_out);
+    assert_eq!(quote_swap(&b.market, &buy.parameters).unwrap().amount_out, route.amount_out);
+    assert!(route.amount_out >= route.min_amount_out);
+    assert_eq!(
+        route.leftovers,
+        vec![(spl_token::native_mint::id(), sell.quote.amount_out - buy.parameters.amount())]
+    );
+    assert!(route.leftovers[0].1 > 0);
+}
+
+#[test]
+fn route_transaction_wraps_sol_only_when_it_is_the_input() {
+    let (a, b) = (sol_market(), sol_market());
+    let markets = [a.clone(), b.clone()];
+    let user = Pubkey::new_unique();
+    let program_ids = |instructions: &[solana_sdk::instruction::Instruction]| {
+        instructions.iter().map(|instruction| instruction.program_id).collect::<Vec<_>>()
+    };
+
+    let route = best_route(&markets, &a.market.token_mint0, &b.market.token_mint0, 1_000_000_000_000, 100, 2).unwrap();
+    let instructions = build_route_instructions(&route, &user, &SwapOptions::default());
+    let ata = spl_associated_token_account::id();
+    let tokenmill = Pubkey::from_str_const(TOKENMILL_PROGRAM);
+    // One token account per mint, then both swaps and the unwrapping of the intermediate wSOL
+    assert_eq!(program_ids(&instructions), vec![ata, ata, ata, tokenmill, tokenmill, spl_token::id()]);
+
+    let route = best_route(&markets, &spl_token::native_mint::id(), &b.market.token_mint0, 1_000_000_000, 100, 2).unwrap();
+    let instructions = build_route_instructions(&route, &user, &SwapOptions::default());
+    assert_eq!(
+        program_ids(&instructions),
+        vec![ata, ata, system_program::id(), spl_token::id(), tokenmill, spl_token::id()]
+    );
+}
@@ -0,0 +1,48 @@
+use solana_sdk::pubkey::Pubkey;
+
+use super::market_with;
+use crate::market::{Market, MarketSettings};
+use crate::quote::Quote;
+use crate::swap::{quote_swap_with_legs, SwapParameters};
+
+fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(value: &T) -> T {
+    serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+}
+
+#[test]
+fn quotes_and_swap_parameters_round_trip_through_json() {
+    let settings = MarketSettings {
+        max_supply: 1_000_000_000_000_000,
+        sqrt_price_a_x96: 1 << 90,
+        sqrt_price_b_x96: 1 << 91,
+        liquidity_a: 1 << 60,
+        liquidity_b: 1 << 58,
+        fee: 10_000,
+    };
+    let market = market_with(settings, (1 << 90) + (1 << 80));
+
+    let parameters = SwapParameters::BuyExactIn(1_000_000_000, 0);
+    let quote = quote_swap_with_legs(&market, &parameters).unwrap();
+    // Wider than a JSON number can hold without losing precision
+    assert!(quote.next_sqrt_price > u128::from(u64::MAX));
+    assert!(!quote.legs.is_empty());
+    assert_eq!(round_trip::<Quote>(&quote), quote);
+    let json = serde_json::to_value(&quote).unwrap();
+    assert_eq!(json["next_sqrt_price"], quote.next_sqrt_price.to_string());
+
+    for parameters in [
+        parameters,
+        SwapParameters::BuyExactOut(1, u64::MAX),
+        SwapParameters::SellExactIn(u64::MAX, 0),
+        SwapParameters::SellExactOut(42, 43),
+    ] {
+        assert_eq!(round_trip::<SwapParameters>(&parameters), parameters);
+    }
+
+    // Optional keys are kept either way
+    let restricted = Market { swap_authority: Some(Pubkey::new_unique()), fee_reserve: None, ..market.clone() };
+    let with_fee_reserve = Market { swap_authority: None, fee_reserve: Some(Pubkey::new_unique()), ..market };
+    for market in [restricted, with_fee_reserve] {
+        assert_eq!(round_trip::<Market>(&market), market);
+    }
+}
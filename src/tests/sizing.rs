@@ -0,0 +1,60 @@
+use proptest::prelude::*;
+use ruint::aliases::U256;
+
+use super::arb_market;
+use crate::price::sqrt_price_x96_to_price;
+use crate::quote::Quote;
+use crate::swap::{quote_swap, QuotedSwap, SwapParameters};
+use crate::sizing::{max_buy_within_budget, max_buy_within_price_impact, max_sell_above_average_price};
+use crate::swap_math::SQRT_PRICE_SHIFT;
+
+proptest! {
+    #[test]
+    fn sizing_solvers_return_the_largest_valid_amount(
+        market in arb_market(),
+        max_price_impact_bps in 1u64..10_000,
+        budget in 1u64..(1u64 << 62),
+        min_average_price_per_mille in 1u128..1_000,
+    ) {
+        let next_amount = |swap: &QuotedSwap| swap.parameters.with_amount(swap.parameters.amount() + 1);
+
+        if let Ok(swap) = max_buy_within_price_impact(&market, max_price_impact_bps) {
+            let max_price = sqrt_price_x96_to_price(market.sqrt_price_x96) * (1.0 + max_price_impact_bps as f64 / 10_000.0);
+            prop_assert!(sqrt_price_x96_to_price(swap.quote.next_sqrt_price) <= max_price * (1.0 + 1e-9));
+            if let Ok(next) = quote_swap(&market, &next_amount(&swap)) {
+                prop_assert!(sqrt_price_x96_to_price(next.next_sqrt_price) >= max_price * (1.0 - 1e-9));
+            }
+        }
+
+        if let Ok(swap) = max_buy_within_budget(&market, budget) {
+            prop_assert!(swap.quote.amount_in <= budget);
+            if !swap.parameters.is_exact_in() {
+                prop_assert_eq!(swap.quote.amount_out, swap.parameters.amount());
+                // Unless the curve can't give any more
+                if let Ok(next) = quote_swap(&market, &next_amount(&swap)) {
+                    prop_assert!(next.amount_in > budget || next.amount_out == swap.quote.amount_out);
+                }
+            }
+            // Never worse than spending the whole budget as an exact in buy
+            if let Ok(exact_in) = quote_swap(&market, &SwapParameters::BuyExactIn(budget, 0)) {
+                prop_assert!(swap.quote.amount_out >= exact_in.amount_out);
+            }
+        }
+
+        // Minimum average price as a fraction of the current price
+        let sqrt_price = U256::from(market.sqrt_price_x96);
+        let min_average_price_x96 = ((sqrt_price * sqrt_price) >> SQRT_PRICE_SHIFT) * U256::from(min_average_price_per_mille) / U256::from(1_000);
+        prop_assume!(min_average_price_x96 < U256::from(u128::MAX));
+        let min_average_price_x96: u128 = min_average_price_x96.to();
+
+        if let Ok(swap) = max_sell_above_average_price(&market, min_average_price_x96) {
+            let clears = |quote: &Quote| {
+                U256::from(quote.amount_out) << SQRT_PRICE_SHIFT >= U256::from(quote.amount_in) * U256::from(min_average_price_x96)
+            };
+            prop_assert!(clears(&swap.quote));
+            if let Ok(next) = quote_swap(&market, &next_amount(&swap)) {
+                prop_assert!(!clears(&next) || next.amount_in < swap.parameters.amount() + 1);
+            }
+        }
+    }
+}
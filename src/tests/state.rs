@@ -0,0 +1,45 @@
+use proptest::prelude::*;
+
+use super::{arb_market, with_sqrt_price};
+use crate::swap::{quote_swap, SwapParameters};
+use crate::state::MarketState;
+use crate::supply::{quote_reserve, tokens_sold};
+
+proptest! {
+    #[test]
+    fn market_state_applies_swaps_like_quotes(
+        market in arb_market(),
+        swaps in prop::collection::vec((any::<bool>(), 1u64..(1u64 << 48)), 1..8),
+    ) {
+        let state = MarketState::new(market.clone());
+        prop_assume!(state.is_ok());
+        let mut state = state.unwrap();
+
+        for (zero_for_one, amount) in swaps {
+            let parameters = if zero_for_one {
+                SwapParameters::SellExactIn(amount, 0)
+            } else {
+                SwapParameters::BuyExactIn(amount, 0)
+            };
+            let expected = quote_swap(&with_sqrt_price(&market, state.sqrt_price_x96()), &parameters);
+            let before = state.clone();
+
+            match state.apply(&parameters) {
+                Ok(fill) => {
+                    prop_assert_eq!(Ok(&fill.quote), expected.as_ref());
+                    prop_assert_eq!(state.sqrt_price_x96(), fill.quote.next_sqrt_price);
+                    prop_assert_eq!(state.swaps, before.swaps + 1);
+                }
+                // Failed swaps leave no trace
+                Err(_) => prop_assert_eq!(&state, &before),
+            }
+        }
+
+        // Each swap rounds in favor of the market, the reserve only rounds up once per phase
+        let settings = &market.settings;
+        let curve_reserve = quote_reserve(settings, state.sqrt_price_x96());
+        prop_assume!(curve_reserve.is_ok());
+        prop_assert!(state.reserve1 - state.fees_token_1 + 2 >= curve_reserve.unwrap());
+        prop_assert!(settings.max_supply - state.reserve0 <= tokens_sold(settings, state.sqrt_price_x96()).unwrap() + 2);
+    }
+}
@@ -0,0 +1,69 @@
+use proptest::prelude::*;
+use ruint::aliases::U256;
+
+use super::{arb_market, arb_settings, curve_settings, market_with};
+use crate::market::MarketSettings;
+use crate::quote::quote;
+use crate::supply::{curve_end_sqrt_price, quote_raised, supply_progress, tokens_sold};
+use crate::swap_math::{get_amount_0, SQRT_PRICE_SHIFT};
+
+proptest! {
+    #[test]
+    fn supply_progress_follows_the_buys(
+        market in arb_market(),
+        amount in 1i64..(1i64 << 62),
+    ) {
+        let settings = &market.settings;
+        let quote = quote(&market, false, amount, u128::MAX);
+        prop_assume!(quote.is_ok());
+        let quote = quote.unwrap();
+
+        let sold_before = tokens_sold(settings, market.sqrt_price_x96);
+        let sold_after = tokens_sold(settings, quote.next_sqrt_price);
+        let raised_before = quote_raised(settings, market.sqrt_price_x96);
+        let raised_after = quote_raised(settings, quote.next_sqrt_price);
+        prop_assume!(sold_after.is_ok() && raised_after.is_ok());
+        let (sold_before, sold_after) = (sold_before.unwrap(), sold_after.unwrap());
+        let (raised_before, raised_after) = (raised_before.unwrap(), raised_after.unwrap());
+
+        // Each of the totals and the swap round once per phase
+        prop_assert!((sold_after - sold_before).abs_diff(quote.amount_out) <= 3);
+        prop_assert!((raised_after - raised_before).abs_diff(quote.amount_in - quote.fee_amount_token_in) <= 3);
+    }
+
+    #[test]
+    fn curve_end_sells_the_whole_supply(
+        settings in arb_settings(),
+        per_mille in 0u128..1_000,
+    ) {
+        // Anywhere up to what the curve can hold, phase B selling at most its liquidity over the boundary price
+        let supply_a = get_amount_0(settings.sqrt_price_a_x96, settings.sqrt_price_b_x96, settings.liquidity_a, false).unwrap();
+        let capacity_b = (U256::from(settings.liquidity_b) << SQRT_PRICE_SHIFT) / U256::from(settings.sqrt_price_b_x96);
+        let capacity = (U256::from(supply_a) + capacity_b) * U256::from(per_mille) / U256::from(1_000u16);
+        let max_supply = u64::try_from(capacity).unwrap_or(u64::MAX).min(i64::MAX as u64);
+        let settings = MarketSettings { max_supply, ..settings };
+        let end_sqrt_price = curve_end_sqrt_price(&settings);
+        prop_assume!(end_sqrt_price.is_ok());
+        let end_sqrt_price = end_sqrt_price.unwrap();
+
+        // The end price is rounded up, so it's the first price where the supply is gone
+        let sold = tokens_sold(&settings, end_sqrt_price);
+        prop_assume!(sold.is_ok());
+        prop_assert!(sold.unwrap().abs_diff(max_supply) <= 2);
+        prop_assert!(end_sqrt_price == settings.sqrt_price_a_x96 || tokens_sold(&settings, end_sqrt_price - 1).unwrap() <= max_supply);
+    }
+}
+
+// Past what phase B can hold, the curve never sells out, but what it sold so far is still known
+#[test]
+fn supply_progress_of_a_curve_that_never_sells_out() {
+    let settings = MarketSettings { max_supply: 100_000_000_000_000_000, ..curve_settings() };
+    let sqrt_price_x96 = 2 * settings.sqrt_price_a_x96;
+    let market = market_with(settings.clone(), sqrt_price_x96);
+
+    let supply = supply_progress(&market).unwrap();
+    assert_eq!(supply.sold, tokens_sold(&settings, sqrt_price_x96).unwrap());
+    assert_eq!(supply.raised, quote_raised(&settings, sqrt_price_x96).unwrap());
+    assert_eq!(supply.remaining, settings.max_supply - supply.sold);
+    assert_eq!((supply.total_raise, supply.end_sqrt_price_x96), (None, None));
+}
@@ -0,0 +1,127 @@
+use proptest::prelude::*;
+use ruint::aliases::{U256, U512};
+
+use crate::math::{mul_div, mul_div_round_up};
+use crate::swap_math::{
+    get_amount_0, get_amount_1, get_delta_amounts, get_next_sqrt_ratio_from_amount_0,
+    get_next_sqrt_ratio_from_amount_1, SQRT_PRICE_SHIFT,
+};
+
+fn u512(value: u128) -> U512 {
+    U512::from(value)
+}
+
+fn q96() -> U512 {
+    U512::from(1u8) << SQRT_PRICE_SHIFT
+}
+
+proptest! {
+    #[test]
+    fn mul_div_rounds_the_exact_product(
+        x in any::<u64>(),
+        y in any::<u32>(),
+        denominator in 1u64..,
+        divisible in any::<bool>(),
+    ) {
+        // Makes the product a multiple of the denominator while `x` alone usually isn't
+        let y = if divisible { U256::from(y) * U256::from(denominator) } else { U256::from(y) };
+        let x = U256::from(x);
+        let (quotient, remainder) = (U512::from(x) * U512::from(y)).div_rem(U512::from(denominator));
+        let ceiling = if remainder.is_zero() { quotient } else { quotient + U512::from(1u8) };
+
+        let down = mul_div(x, y, U256::from(denominator)).unwrap();
+        let up = mul_div_round_up(x, y, U256::from(denominator)).unwrap();
+
+        prop_assert_eq!(U512::from(down), quotient);
+        prop_assert_eq!(U512::from(up), ceiling);
+    }
+
+    #[test]
+    fn get_amounts_round_in_favor_of_the_pool(
+        sqrt_price_a in (1u128 << 64)..(1u128 << 110),
+        delta in 1u128..(1u128 << 100),
+        liquidity in 1u128..(1u128 << 90),
+    ) {
+        let sqrt_price_b = sqrt_price_a + delta;
+
+        // amount_0 = liquidity * 2^96 * (b - a) / (a * b)
+        let numerator = (u512(liquidity) << SQRT_PRICE_SHIFT) * u512(delta);
+        let denominator = u512(sqrt_price_a) * u512(sqrt_price_b);
+        if let (Ok(down), Ok(up)) = (
+            get_amount_0(sqrt_price_a, sqrt_price_b, liquidity, false),
+            get_amount_0(sqrt_price_a, sqrt_price_b, liquidity, true),
+        ) {
+            prop_assert!(u512(down) * denominator <= numerator);
+            prop_assert!(u512(up) * denominator >= numerator);
+            prop_assert!(up - down <= 1);
+        }
+
+        // amount_1 = liquidity * (b - a) / 2^96
+        let numerator = u512(liquidity) * u512(delta);
+        if let (Ok(down), Ok(up)) = (
+            get_amount_1(sqrt_price_a, sqrt_price_b, liquidity, false),
+            get_amount_1(sqrt_price_a, sqrt_price_b, liquidity, true),
+        ) {
+            prop_assert!(u512(down) * q96() <= numerator);
+            prop_assert!(u512(up) * q96() >= numerator);
+            prop_assert!(up - down <= 1);
+        }
+    }
+
+    #[test]
+    fn next_sqrt_ratio_never_moves_more_than_paid_for(
+        sqrt_price in (1u128 << 64)..(1u128 << 110),
+        liquidity in 1u128..(1u128 << 90),
+        amount in 1i64..i64::MAX,
+    ) {
+        // Token 1 in: the price goes up
+        if let Ok(next_sqrt_price) = get_next_sqrt_ratio_from_amount_1(sqrt_price, liquidity, amount) {
+            if let Ok(required) = get_amount_1(sqrt_price, next_sqrt_price, liquidity, true) {
+                prop_assert!(required <= amount as u128);
+            }
+        }
+
+        // Token 0 in: the price goes down
+        if let Ok(next_sqrt_price) = get_next_sqrt_ratio_from_amount_0(sqrt_price, liquidity, amount) {
+            if let Ok(required) = get_amount_0(next_sqrt_price, sqrt_price, liquidity, true) {
+                prop_assert!(required <= amount as u128);
+            }
+        }
+    }
+
+    #[test]
+    fn get_delta_amounts_round_in_favor_of_the_pool(
+        sqrt_price in (1u128 << 80)..(1u128 << 110),
+        liquidity in (1u128 << 40)..(1u128 << 90),
+        amount in 1u64..(1u64 << 62),
+    ) {
+        let p = u512(sqrt_price);
+        let l = u512(liquidity);
+        let x = u512(u128::from(amount));
+        let l_q96 = l * q96();
+
+        // Buy exact in: out <= 2^192 * x * l / (p * (p * l + x * 2^96))
+        if let Ok((next, _, out, _)) = get_delta_amounts(sqrt_price, u128::MAX, liquidity, amount as i64, 0) {
+            prop_assume!(next != u128::MAX);
+            prop_assert!(u512(u128::from(out)) * p * (p * l + x * q96()) <= q96() * q96() * x * l);
+        }
+
+        // Sell exact in: out <= l * x * p^2 / (2^96 * (l * 2^96 + x * p))
+        if let Ok((next, _, out, _)) = get_delta_amounts(sqrt_price, 1, liquidity, amount as i64, 0) {
+            prop_assume!(next != 1);
+            prop_assert!(u512(u128::from(out)) * q96() * (l_q96 + x * p) <= l * x * p * p);
+        }
+
+        // Buy exact out: in * 2^96 * (l * 2^96 - x * p) >= l * x * p^2
+        if let Ok((_, amount_in, out, _)) = get_delta_amounts(sqrt_price, u128::MAX, liquidity, -(amount as i64), 0) {
+            prop_assume!(out == amount && l_q96 > x * p);
+            prop_assert!(u512(u128::from(amount_in)) * q96() * (l_q96 - x * p) >= l * x * p * p);
+        }
+
+        // Sell exact out: in * p * (p * l - x * 2^96) >= 2^192 * x * l
+        if let Ok((_, amount_in, out, _)) = get_delta_amounts(sqrt_price, 1, liquidity, -(amount as i64), 0) {
+            prop_assume!(out == amount && p * l > x * q96());
+            prop_assert!(u512(u128::from(amount_in)) * p * (p * l - x * q96()) >= q96() * q96() * x * l);
+        }
+    }
+}
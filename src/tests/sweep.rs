@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use solana_sdk::pubkey::Pubkey;
+
+use super::{mid_curve_market, restricted_market};
+use crate::market::{Market, TOKENMILL_PROGRAM};
+use crate::portfolio::{PortfolioPosition, TokenHolding};
+use crate::sweep::{build_sweep_transactions, plan_sweep};
+use crate::swap::SwapOptions;
+use crate::token::get_associated_token_address;
+
+#[test]
+fn sweeps_sell_dust_then_close_the_empty_accounts() {
+    let owner = Pubkey::new_unique();
+    let mut market = mid_curve_market();
+    market.token_mint1 = spl_token::native_mint::id();
+    let restricted = restricted_market(&Pubkey::new_unique());
+
+    let position = |market: &Market, amount, associated: bool| {
+        let address = if associated {
+            get_associated_token_address(&owner, &market.token_mint0, &spl_token_2022::id())
+        } else {
+            Pubkey::new_unique()
+        };
+        let holding = TokenHolding {
+            address,
+            mint: market.token_mint0,
+            token_program: spl_token_2022::id(),
+            amount,
+            lamports: 2_039_280,
+        };
+        PortfolioPosition::new(holding, Some((Pubkey::new_unique(), market.clone())))
+    };
+    let positions = [
+        position(&market, 10_000_000_000, true),
+        position(&market, 100_000_000_000_000, false),
+        position(&restricted, 10_000_000_000, true),
+        position(&market, 10_000_000_000, false),
+        position(&market, 0, false),
+        position(&market, u64::MAX, false),
+        // SOL already wrapped, on top of the rent
+        PortfolioPosition::new(
+            TokenHolding {
+                address: get_associated_token_address(&owner, &spl_token::native_mint::id(), &spl_token::id()),
+                mint: spl_token::native_mint::id(),
+                token_program: spl_token::id(),
+                amount: 5_000_000,
+                lamports: 2_039_280 + 5_000_000,
+            },
+            None,
+        ),
+    ];
+    let token_programs1 = HashMap::from([(spl_token::native_mint::id(), spl_token::id())]);
+
+    let plan = plan_sweep(&positions, &owner, &token_programs1, 1_000_000, 100).unwrap();
+    assert_eq!(plan.sales.len(), 1);
+    assert_eq!(plan.sales[0].holding, positions[0].holding);
+    assert_eq!(plan.skipped.len(), 3);
+    assert!(plan.skipped[2].reason.starts_with("Not valued"));
+    // The empty account, then the wSOL account the sale fills
+    assert_eq!(plan.closes.len(), 2);
+    assert_eq!(plan.closes[1], positions[6].holding);
+    assert_eq!(plan.reclaimed_lamports(), 3 * 2_039_280);
+    assert_eq!(plan.unwrapped_lamports(), 5_000_000);
+
+    let transactions = build_sweep_transactions(&plan, &owner, &SwapOptions::default()).unwrap();
+    assert_eq!(transactions.len(), 2);
+    let program = Pubkey::from_str_const(TOKENMILL_PROGRAM);
+    assert!(transactions[0].message.account_keys.contains(&program));
+    assert!(!transactions[1].message.account_keys.contains(&program));
+
+    // Sales never share a transaction, so that each one closes its account only if it went through
+    let mut plan = plan;
+    plan.sales.push(plan.sales[0].clone());
+    let transactions = build_sweep_transactions(&plan, &owner, &SwapOptions::default()).unwrap();
+    assert_eq!(transactions.len(), 3);
+    for transaction in &transactions[..2] {
+        let message = &transaction.message;
+        let programs = message
+            .instructions
+            .iter()
+            .map(|instruction| message.account_keys[instruction.program_id_index as usize])
+            .collect::<Vec<_>>();
+        assert_eq!(programs, vec![spl_associated_token_account::id(), program, spl_token_2022::id()]);
+    }
+}
@@ -0,0 +1,40 @@
+use proptest::prelude::*;
+
+use super::arb_market;
+use crate::swap::quote_swap;
+use crate::target::quote_to_sqrt_price;
+
+proptest! {
+    #[test]
+    fn quote_to_sqrt_price_stops_at_the_closest_price_above_the_target(
+        market in arb_market(),
+        position in 0u128..=3_000,
+    ) {
+        let settings = &market.settings;
+        let span = 3 * (settings.sqrt_price_b_x96 - settings.sqrt_price_a_x96);
+        let target_sqrt_price = settings.sqrt_price_a_x96 + span * position / 3_000;
+
+        let target = quote_to_sqrt_price(&market, target_sqrt_price);
+        prop_assume!(target.is_ok());
+        let target = target.unwrap();
+        let amount = target.parameters.amount();
+
+        prop_assert_eq!(quote_swap(&market, &target.parameters), Ok(target.quote.clone()));
+        prop_assert!(target.quote.next_sqrt_price >= target_sqrt_price || amount == 0);
+
+        // One unit more of a sell or less of a buy ends below the target
+        let next = if target.parameters.zero_for_one() {
+            target.parameters.with_amount(amount + 1)
+        } else {
+            prop_assume!(amount > 0);
+            target.parameters.with_amount(amount - 1)
+        };
+        if let Ok(next) = quote_swap(&market, &next) {
+            // Unless the sell already empties the curve
+            prop_assert!(
+                next.next_sqrt_price < target_sqrt_price
+                    || (target.parameters.zero_for_one() && next.next_sqrt_price == target.quote.next_sqrt_price)
+            );
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use super::mid_curve_market;
+use crate::swap::{quote_swap, SwapParameters};
+use crate::tracker::realized_slippage_bps;
+
+#[test]
+fn realized_slippage_is_positive_when_the_fill_is_worse_than_quoted() {
+    let market = mid_curve_market();
+
+    let buy = SwapParameters::BuyExactIn(1_000_000, 0);
+    let quote = quote_swap(&market, &buy).unwrap();
+    assert_eq!(realized_slippage_bps(&buy, &quote, quote.amount_in, quote.amount_out), 0.0);
+    let slippage = realized_slippage_bps(&buy, &quote, quote.amount_in, quote.amount_out * 99 / 100);
+    assert!((slippage - 100.0).abs() < 0.01);
+    assert!(realized_slippage_bps(&buy, &quote, quote.amount_in, quote.amount_out + 1) < 0.0);
+
+    // Exact out swaps slip on what they spend
+    let buy = SwapParameters::BuyExactOut(1_000_000, 0);
+    let quote = quote_swap(&market, &buy).unwrap();
+    assert!(realized_slippage_bps(&buy, &quote, quote.amount_in + 1, quote.amount_out) > 0.0);
+    assert!(realized_slippage_bps(&buy, &quote, quote.amount_in - 1, quote.amount_out) < 0.0);
+}
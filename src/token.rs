@@ -0,0 +1,18 @@
+use solana_sdk::pubkey::Pubkey;
+
+pub fn get_associated_token_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &spl_associated_token_account::id(),
+    )
+    .0
+}
+
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::id()
+}
+
+// Amount of a token account, at the same offset for SPL and Token 2022 accounts, extensions coming after the base layout
+pub fn token_account_amount(data: &[u8]) -> Option<u64> {
+    data.get(64..72).map(|amount| u64::from_le_bytes(amount.try_into().unwrap()))
+}
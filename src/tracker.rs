@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Context, Result};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature, transaction::TransactionError,
+};
+
+use crate::quote::Quote;
+use crate::swap::{SwapParameters, MAX_BPS};
+use crate::transaction::{decode_swaps, fetch_transaction};
+
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Where a sent transaction ended up
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Confirmation {
+    Confirmed { slot: u64 },
+    Failed { slot: u64, err: TransactionError },
+    // Not seen at the requested commitment before its blockhash expired, it can't land anymore
+    Expired,
+}
+
+// Polls the signature status until it reaches `commitment`, fails, or the chain goes past `last_valid_block_height`
+// Polling rather than `signatureSubscribe` needs no websocket
+pub async fn wait_for_confirmation(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    commitment: CommitmentConfig,
+    last_valid_block_height: u64,
+    poll_interval: Duration,
+) -> Result<Confirmation> {
+    loop {
+        // The block height is read first, so a status landing in between is still seen before giving up
+        let expired = rpc_client.get_block_height().await? > last_valid_block_height;
+
+        let status = rpc_client
+            .get_signature_statuses(&[*signature])
+            .await?
+            .value
+            .into_iter()
+            .next()
+            .flatten();
+        if let Some(status) = status {
+            if let Some(err) = status.err {
+                return Ok(Confirmation::Failed { slot: status.slot, err });
+            }
+            if status.satisfies_commitment(commitment) {
+                return Ok(Confirmation::Confirmed { slot: status.slot });
+            }
+        }
+
+        if expired {
+            return Ok(Confirmation::Expired);
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+// A sent swap compared with the quote it was built from
+#[derive(Clone, Debug, PartialEq)]
+pub struct FillReport {
+    pub signature: Signature,
+    pub confirmation: Confirmation,
+    pub parameters: SwapParameters,
+    pub quote: Quote,
+    // Only known once confirmed, and when the transaction holds no other swap on the same mints
+    pub amount_in: Option<u64>,
+    pub amount_out: Option<u64>,
+    // How much worse than quoted the fill got on its free side, in bps of the quoted amount: less out for exact in
+    // swaps, more in for exact out swaps, negative when the fill did better
+    pub realized_slippage_bps: Option<f64>,
+}
+
+impl FillReport {
+    pub fn to_json(&self) -> Value {
+        let (status, slot, error) = match &self.confirmation {
+            Confirmation::Confirmed { slot } => ("confirmed", Some(*slot), None),
+            Confirmation::Failed { slot, err } => ("failed", Some(*slot), Some(err.to_string())),
+            Confirmation::Expired => ("expired", None, None),
+        };
+
+        json!({
+            "signature": self.signature.to_string(),
+            "status": status,
+            "slot": slot,
+            "error": error,
+            "side": self.parameters.side(),
+            "mode": self.parameters.mode(),
+            "threshold": self.parameters.threshold(),
+            "quoted_amount_in": self.quote.amount_in,
+            "quoted_amount_out": self.quote.amount_out,
+            "amount_in": self.amount_in,
+            "amount_out": self.amount_out,
+            "realized_slippage_bps": self.realized_slippage_bps,
+        })
+    }
+}
+
+pub fn realized_slippage_bps(parameters: &SwapParameters, quote: &Quote, amount_in: u64, amount_out: u64) -> f64 {
+    let (quoted, slippage) = if parameters.is_exact_in() {
+        (quote.amount_out as f64, quote.amount_out as f64 - amount_out as f64)
+    } else {
+        (quote.amount_in as f64, amount_in as f64 - quote.amount_in as f64)
+    };
+
+    slippage / quoted * MAX_BPS as f64
+}
+
+// A swap transaction as it was sent, along with what it was built from
+#[derive(Clone, Debug, PartialEq)]
+pub struct SentSwap {
+    pub signature: Signature,
+    pub market: Pubkey,
+    pub user: Pubkey,
+    pub parameters: SwapParameters,
+    pub quote: Quote,
+    // Of the blockhash the transaction was signed with
+    pub last_valid_block_height: u64,
+}
+
+// Waits for the swap, then reads what it actually filled from the confirmed transaction
+// Transactions are only served once confirmed, so a processed commitment waits for confirmed
+pub async fn track_swap(rpc_client: &RpcClient, sent: &SentSwap, commitment: CommitmentConfig) -> Result<FillReport> {
+    let SentSwap { signature, market, user, parameters, quote, last_valid_block_height } = sent;
+    let commitment = if commitment.is_at_least_confirmed() { commitment } else { CommitmentConfig::confirmed() };
+    let confirmation =
+        wait_for_confirmation(rpc_client, signature, commitment, *last_valid_block_height, DEFAULT_POLL_INTERVAL)
+            .await?;
+    let mut report = FillReport {
+        signature: *signature,
+        confirmation,
+        parameters: parameters.clone(),
+        quote: quote.clone(),
+        amount_in: None,
+        amount_out: None,
+        realized_slippage_bps: None,
+    };
+    if !matches!(report.confirmation, Confirmation::Confirmed { .. }) {
+        return Ok(report);
+    }
+
+    let transaction = fetch_transaction(rpc_client, signature).await?;
+    let swap = decode_swaps(&transaction)?
+        .into_iter()
+        .find(|swap| swap.market == *market && swap.user == *user)
+        .with_context(|| format!("No swap of {} on market {} in {}", user, market, signature))?;
+    if swap.parameters != *parameters {
+        return Err(anyhow!("Swap of {} doesn't match the parameters it was built with", signature));
+    }
+
+    if let (Some(amount_in), Some(amount_out)) = (swap.amount_in, swap.amount_out) {
+        report.realized_slippage_bps = Some(realized_slippage_bps(parameters, quote, amount_in, amount_out));
+    }
+    report.amount_in = swap.amount_in;
+    report.amount_out = swap.amount_out;
+
+    Ok(report)
+}
@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Context, Result};
+use borsh::BorshDeserialize;
+use std::{collections::HashMap, str::FromStr};
+
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
+use solana_sdk::{bs58, commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta, UiInstruction,
+    UiTransactionEncoding, UiTransactionTokenBalance,
+};
+
+use crate::market::TOKENMILL_PROGRAM;
+use crate::swap::{SwapParameters, SWAP_DISCRIMINATOR};
+
+// Instruction of a confirmed transaction with its account indexes resolved to keys
+#[derive(Clone, Debug)]
+pub struct ExecutedInstruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<Pubkey>,
+    pub data: Vec<u8>,
+}
+
+// A TokenMill swap executed by a confirmed transaction
+// Amounts are the ones seen by the user, they are None when the transaction holds several swaps on the same mints,
+// as balance changes can't be split between them
+#[derive(Clone, Debug)]
+pub struct ExecutedSwap {
+    pub market: Pubkey,
+    pub token_mint0: Pubkey,
+    pub token_mint1: Pubkey,
+    pub user: Pubkey,
+    pub parameters: SwapParameters,
+    pub amount_in: Option<u64>,
+    pub amount_out: Option<u64>,
+}
+
+pub async fn fetch_transaction(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+    rpc_client
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await
+        .with_context(|| format!("Failed to fetch transaction {}", signature))
+}
+
+// Static keys followed by the writable then readonly keys loaded from lookup tables
+pub fn account_keys(transaction: &EncodedConfirmedTransactionWithStatusMeta) -> Result<Vec<Pubkey>> {
+    let decoded = transaction
+        .transaction
+        .transaction
+        .decode()
+        .context("Failed to decode transaction")?;
+    let mut keys = decoded.message.static_account_keys().to_vec();
+
+    if let Some(meta) = transaction.transaction.meta.as_ref() {
+        if let OptionSerializer::Some(loaded_addresses) = meta.loaded_addresses.as_ref() {
+            for address in loaded_addresses.writable.iter().chain(loaded_addresses.readonly.iter()) {
+                keys.push(Pubkey::from_str(address).map_err(|_| anyhow!("Invalid address: {}", address))?);
+            }
+        }
+    }
+
+    Ok(keys)
+}
+
+// Top level and inner instructions, in execution order
+pub fn executed_instructions(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<Vec<ExecutedInstruction>> {
+    let decoded = transaction
+        .transaction
+        .transaction
+        .decode()
+        .context("Failed to decode transaction")?;
+    let keys = account_keys(transaction)?;
+    let key = |index: u8| {
+        keys.get(index as usize)
+            .copied()
+            .ok_or(anyhow!("Invalid account index: {}", index))
+    };
+
+    let mut inner_instructions = HashMap::new();
+    if let Some(meta) = transaction.transaction.meta.as_ref() {
+        if let OptionSerializer::Some(inner) = meta.inner_instructions.as_ref() {
+            for set in inner {
+                inner_instructions.insert(set.index, &set.instructions);
+            }
+        }
+    }
+
+    let mut instructions = vec![];
+    for (index, instruction) in decoded.message.instructions().iter().enumerate() {
+        instructions.push(ExecutedInstruction {
+            program_id: key(instruction.program_id_index)?,
+            accounts: instruction.accounts.iter().map(|index| key(*index)).collect::<Result<_>>()?,
+            data: instruction.data.clone(),
+        });
+
+        for inner in inner_instructions.get(&(index as u8)).into_iter().flat_map(|set| set.iter()) {
+            // Only the compiled form is returned for the base64 encoding
+            let UiInstruction::Compiled(inner) = inner else {
+                continue;
+            };
+            instructions.push(ExecutedInstruction {
+                program_id: key(inner.program_id_index)?,
+                accounts: inner.accounts.iter().map(|index| key(*index)).collect::<Result<_>>()?,
+                data: bs58::decode(&inner.data)
+                    .into_vec()
+                    .ok()
+                    .context("Invalid inner instruction data")?,
+            });
+        }
+    }
+
+    Ok(instructions)
+}
+
+// Decodes a TokenMill swap instruction, accounts are the ones of `swap::build_swap_instruction`
+// Amounts are left empty as they only come from the transaction balances
+pub fn decode_swap_instruction(instruction: &ExecutedInstruction) -> Option<ExecutedSwap> {
+    if instruction.program_id != Pubkey::from_str_const(TOKENMILL_PROGRAM)
+        || !instruction.data.starts_with(&SWAP_DISCRIMINATOR)
+        || instruction.accounts.len() < 11
+    {
+        return None;
+    }
+
+    let parameters = SwapParameters::try_from_slice(&instruction.data[SWAP_DISCRIMINATOR.len()..]).ok()?;
+
+    Some(ExecutedSwap {
+        market: instruction.accounts[1],
+        token_mint0: instruction.accounts[2],
+        token_mint1: instruction.accounts[3],
+        user: instruction.accounts[10],
+        parameters,
+        amount_in: None,
+        amount_out: None,
+    })
+}
+
+pub fn decode_swaps(transaction: &EncodedConfirmedTransactionWithStatusMeta) -> Result<Vec<ExecutedSwap>> {
+    let meta = transaction
+        .transaction
+        .meta
+        .as_ref()
+        .context("Transaction has no status meta")?;
+    if let Some(err) = meta.err.as_ref() {
+        return Err(anyhow!("Transaction failed: {}", err));
+    }
+
+    let mut swaps = executed_instructions(transaction)?
+        .iter()
+        .filter_map(decode_swap_instruction)
+        .collect::<Vec<_>>();
+
+    let mut swaps_per_mint = HashMap::<Pubkey, usize>::new();
+    for swap in swaps.iter() {
+        *swaps_per_mint.entry(swap.token_mint0).or_default() += 1;
+        *swaps_per_mint.entry(swap.token_mint1).or_default() += 1;
+    }
+
+    for swap in swaps.iter_mut() {
+        let (mint_in, mint_out) = if swap.parameters.zero_for_one() {
+            (swap.token_mint0, swap.token_mint1)
+        } else {
+            (swap.token_mint1, swap.token_mint0)
+        };
+        if swaps_per_mint[&mint_in] > 1 || swaps_per_mint[&mint_out] > 1 {
+            continue;
+        }
+
+        // What the user paid ended up in accounts it doesn't own, and the other way around,
+        // which holds even for token accounts created and closed within the transaction
+        let amount_in = token_balance_change(meta.pre_token_balances.as_ref(), meta.post_token_balances.as_ref(), &mint_in, &swap.user)?;
+        let amount_out = -token_balance_change(meta.pre_token_balances.as_ref(), meta.post_token_balances.as_ref(), &mint_out, &swap.user)?;
+
+        swap.amount_in = Some(u64::try_from(amount_in).map_err(|_| anyhow!("Invalid amount in: {}", amount_in))?);
+        swap.amount_out = Some(u64::try_from(amount_out).map_err(|_| anyhow!("Invalid amount out: {}", amount_out))?);
+    }
+
+    Ok(swaps)
+}
+
+// Net change of the balances of `mint` held by accounts not owned by `user`
+fn token_balance_change(
+    pre_token_balances: OptionSerializer<&Vec<UiTransactionTokenBalance>>,
+    post_token_balances: OptionSerializer<&Vec<UiTransactionTokenBalance>>,
+    mint: &Pubkey,
+    user: &Pubkey,
+) -> Result<i128> {
+    let mint = mint.to_string();
+    let user = user.to_string();
+
+    let sum = |balances: OptionSerializer<&Vec<UiTransactionTokenBalance>>| -> Result<i128> {
+        let OptionSerializer::Some(balances) = balances else {
+            return Err(anyhow!("Transaction has no token balances"));
+        };
+
+        balances
+            .iter()
+            .filter(|balance| balance.mint == mint && balance.owner != OptionSerializer::Some(user.clone()))
+            .map(|balance| {
+                balance
+                    .ui_token_amount
+                    .amount
+                    .parse::<i128>()
+                    .map_err(|_| anyhow!("Invalid token amount: {}", balance.ui_token_amount.amount))
+            })
+            .sum()
+    };
+
+    Ok(sum(post_token_balances)? - sum(pre_token_balances)?)
+}